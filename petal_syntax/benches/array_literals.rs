@@ -0,0 +1,26 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+fn program(length: usize) -> String {
+    let elements = (0..length).map(|i| i.to_string()).collect::<Vec<_>>();
+    format!("x = [{}]\nx\n", elements.join(" "))
+}
+
+// Array literals get built up one element at a time, so parsing one should
+// take time proportional to the number of elements, and the throughput should
+// stay about the same as the array gets longer.
+fn parse_array_literal(c: &mut Criterion) {
+    let mut group = c.benchmark_group("parse array literal");
+    for length in [1_000, 10_000, 100_000] {
+        let program = program(length);
+        group.throughput(Throughput::Elements(length as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(length),
+            &program,
+            |b, program| b.iter(|| petal_syntax::parse(program).unwrap()),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, parse_array_literal);
+criterion_main!(benches);
@@ -0,0 +1,56 @@
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use petal_syntax::dump::{dump, Stage};
+
+const PUNCTUATION: &[char] = &['<', '>', '|', '&', '^', '~', '!', '@', '$', '%'];
+
+// A few dozen made-up operators of different lengths, half of them defined at
+// the top level and half in a nested block, and a long soup made out of all of
+// them.
+fn program(soup_length: usize) -> String {
+    let operators = (0..48)
+        .map(|i| {
+            let length = 2 + i % 3;
+            (0..length)
+                .map(|j| PUNCTUATION[(i * 7 + j * 3) % PUNCTUATION.len()])
+                .collect::<String>()
+        })
+        .collect::<Vec<_>>();
+    let mut soup = String::new();
+    for op in operators.iter().cycle() {
+        if soup.len() >= soup_length {
+            break;
+        }
+        soup += op;
+    }
+
+    let (outer, inner) = operators.split_at(operators.len() / 2);
+    let mut program = String::new();
+    for op in outer {
+        program += &format!("{} = +\n", op);
+    }
+    program += "x =\n";
+    for op in inner {
+        program += &format!("  {} = +\n", op);
+    }
+    program += &format!("  1 {} 2\nx\n", soup);
+    program
+}
+
+// Splitting should take time proportional to the length of the soup, so the
+// throughput should stay about the same as the soup gets longer.
+fn split_soup(c: &mut Criterion) {
+    let mut group = c.benchmark_group("split soup");
+    for length in [1_000, 10_000, 100_000] {
+        let program = program(length);
+        group.throughput(Throughput::Bytes(length as u64));
+        group.bench_with_input(
+            BenchmarkId::from_parameter(length),
+            &program,
+            |b, program| b.iter(|| dump(program, Stage::Soupless).unwrap()),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, split_soup);
+criterion_main!(benches);
@@ -1,18 +1,40 @@
+use crate::location::Location;
 use crate::statement::*;
-use crate::terms::{SouplessTerm, Term};
+use crate::terms::{Delimiters, Name, SouplessTerm, Term};
 
 fn convert(t: SouplessTerm) -> Term {
     match t {
         SouplessTerm::Identifier(s) => Term::Identifier(s),
         SouplessTerm::Operator(s) => Term::Identifier(s),
         SouplessTerm::NumericLiteral(s) => Term::NumericLiteral(s),
-        SouplessTerm::Parens(terms) => Term::Parens(group(terms)),
-        SouplessTerm::Brackets(terms) => Term::Brackets(group(terms)),
+        SouplessTerm::Parens(terms, delimiters) => Term::Parens(group(terms), delimiters),
+        SouplessTerm::Brackets(terms, delimiters) => Term::Brackets(group(terms), delimiters),
+        SouplessTerm::UnknownOperator(soup, splits) => Term::UnknownOperator(soup, splits),
         SouplessTerm::Space => panic!(),
-        SouplessTerm::MinusOperator => Term::Identifier("-".to_string()),
+        SouplessTerm::MinusOperator(location) => Term::Identifier(Name {
+            name: "-".to_string(),
+            location,
+        }),
     }
 }
 
+// Wraps a coefficient and the term that it scales in parens that span them
+// both.
+fn scaled(coefficient: Term, start: Location, term: SouplessTerm) -> Term {
+    let end = match &term {
+        SouplessTerm::Identifier(name) => name.location,
+        SouplessTerm::Parens(_, delimiters) | SouplessTerm::Brackets(_, delimiters) => {
+            delimiters.close
+        }
+        _ => unreachable!(),
+    };
+    let delimiters = Delimiters {
+        open: start,
+        close: end,
+    };
+    Term::Parens(vec![coefficient, convert(term)], delimiters)
+}
+
 pub(super) fn group(terms: Vec<SouplessTerm>) -> Vec<Term> {
     let mut result = vec![];
     let mut iterator = terms.into_iter().peekable();
@@ -21,33 +43,48 @@ pub(super) fn group(terms: Vec<SouplessTerm>) -> Vec<Term> {
     while let Some(next) = iterator.next() {
         match (next, iterator.peek()) {
             (
-                minus @ MinusOperator,
-                Some(MinusOperator | Operator(_) | Space | NumericLiteral(_)) | None,
+                minus @ MinusOperator(_),
+                Some(
+                    MinusOperator(_)
+                    | Operator(_)
+                    | UnknownOperator(_, _)
+                    | Space
+                    | NumericLiteral(_),
+                )
+                | None,
             ) => result.push(convert(minus)),
-            (MinusOperator, Some(Identifier(_) | Parens(_) | Brackets(_))) => {
-                result.push(Term::Parens(vec![
-                    Term::Coefficient("-1".to_string()),
-                    convert(iterator.next().unwrap()),
-                ]))
+            (MinusOperator(location), Some(Identifier(_) | Parens(_, _) | Brackets(_, _))) => {
+                let minus = Name {
+                    name: "-".to_string(),
+                    location,
+                };
+                let coefficient = Term::Coefficient("-1".to_string(), minus);
+                result.push(scaled(coefficient, location, iterator.next().unwrap()))
             }
             (
                 num @ NumericLiteral(_),
-                Some(MinusOperator | Operator(_) | Space | NumericLiteral(_)) | None,
+                Some(
+                    MinusOperator(_)
+                    | Operator(_)
+                    | UnknownOperator(_, _)
+                    | Space
+                    | NumericLiteral(_),
+                )
+                | None,
             ) => result.push(convert(num)),
-            (NumericLiteral(c), Some(Identifier(_) | Parens(_) | Brackets(_))) => {
-                result.push(Term::Parens(vec![
-                    Term::Coefficient(c),
-                    convert(iterator.next().unwrap()),
-                ]))
+            (NumericLiteral(c), Some(Identifier(_) | Parens(_, _) | Brackets(_, _))) => {
+                let location = c.location;
+                let coefficient = Term::Coefficient(c.name.clone(), c);
+                result.push(scaled(coefficient, location, iterator.next().unwrap()))
             }
             (SouplessTerm::Space, _) => (),
-            (term @ (Identifier(_) | Parens(_) | Brackets(_)), lookahead) => {
+            (term @ (Identifier(_) | Parens(_, _) | Brackets(_, _)), lookahead) => {
                 result.push(convert(term));
-                if let Some(SouplessTerm::MinusOperator) = lookahead {
+                if let Some(SouplessTerm::MinusOperator(_)) = lookahead {
                     result.push(convert(iterator.next().unwrap()))
                 }
             }
-            (op @ Operator(_), _) => result.push(convert(op)),
+            (op @ (Operator(_) | UnknownOperator(_, _)), _) => result.push(convert(op)),
         }
     }
     result
@@ -74,16 +111,17 @@ mod tests {
 
     fn show_term(term: Term) -> String {
         match term {
-            Term::Identifier(id) => id,
-            Term::NumericLiteral(id) => id,
-            Term::Coefficient(c) => format!("<scale {}>", c),
-            Term::Parens(terms) => delimited("(", terms, ")"),
-            Term::Brackets(terms) => delimited("[", terms, "]"),
+            Term::Identifier(id) => id.name,
+            Term::NumericLiteral(num) => num.name,
+            Term::Coefficient(c, _) => format!("<scale {}>", c),
+            Term::UnknownOperator(soup, _) => format!("<unknown {}>", soup),
+            Term::Parens(terms, _) => delimited("(", terms, ")"),
+            Term::Brackets(terms, _) => delimited("[", terms, "]"),
         }
     }
 
     fn test(input: &str) -> String {
-        let tokens = crate::tokenizer::tokenize(input);
+        let tokens = crate::tokenizer::tokenize(input).unwrap();
         let terms = crate::statement_parser::parse_expression(tokens).unwrap();
         let terms = crate::semicolons::resolve_expression(terms);
         let terms = crate::op_splitter::split_expression(terms);
@@ -162,9 +200,17 @@ pub(super) fn rewrite(block: Block<SouplessTerm>) -> Block<Term> {
     block
         .into_iter()
         .map(|statement: Statement<SouplessTerm>| match statement {
-            SimpleAssignment(id, terms) => SimpleAssignment(id, group(terms)),
-            CompoundAssignment(id, block) => CompoundAssignment(id, rewrite(block)),
+            SimpleAssignment(id, sig, terms) => SimpleAssignment(id, sig, group(terms)),
+            CompoundAssignment(id, sig, block) => CompoundAssignment(id, sig, rewrite(block)),
             Expression(terms) => Expression(group(terms)),
+            Declaration(id, sig) => Declaration(id, sig),
+            Destructuring(names, terms) => Destructuring(names, group(terms)),
+            Conditional(terms, consequent, otherwise, alternative) => Conditional(
+                group(terms),
+                rewrite(consequent),
+                otherwise,
+                rewrite(alternative),
+            ),
         })
         .collect()
 }
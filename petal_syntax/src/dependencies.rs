@@ -0,0 +1,400 @@
+use crate::expression::{Expression, Identifier, RichIdentifier};
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+// The definition-use graph of a parsed program. Every assignment and every
+// parameter is a definition, including definitions in nested blocks. A
+// definition uses every identifier that appears in its body, except for the
+// identifiers defined by blocks nested inside of it -- those are definitions
+// of their own, and the outer definition only uses the ones that its result
+// refers to.
+#[derive(Debug)]
+pub struct DependencyGraph {
+    definitions: Vec<RichIdentifier>,
+    uses: HashMap<Identifier, Vec<RichIdentifier>>,
+    result_uses: Vec<RichIdentifier>,
+}
+
+struct Walker {
+    definitions: Vec<RichIdentifier>,
+    uses: HashMap<Identifier, Vec<RichIdentifier>>,
+}
+
+impl Walker {
+    fn define(&mut self, id: &RichIdentifier) {
+        self.definitions.push(id.clone());
+        self.uses.insert(id.id, vec![]);
+    }
+
+    fn walk(&mut self, expr: &Expression, uses: &mut Vec<RichIdentifier>) {
+        use Expression::*;
+        match expr {
+            Compound(assignments, result) => {
+                for (id, body) in assignments {
+                    self.define(id);
+                    let mut body_uses = vec![];
+                    self.walk(body, &mut body_uses);
+                    self.uses.insert(id.id, body_uses);
+                }
+                self.walk(result, uses);
+            }
+            Lambda(params, body, _) => {
+                for (param, _) in params {
+                    self.define(param);
+                }
+                self.walk(body, uses);
+            }
+            Conditional(condition, consequent, alternative) => {
+                self.walk(condition, uses);
+                self.walk(consequent, uses);
+                self.walk(alternative, uses);
+            }
+            Identifier(id) => {
+                if !uses.contains(id) {
+                    uses.push(id.clone());
+                }
+            }
+            Implicit(_) | NumericLiteral(_) => (),
+            Parens(expr) | Element(expr, _, _) => self.walk(expr, uses),
+            Tuple(exprs) | Brackets(exprs) => {
+                for expr in exprs {
+                    self.walk(expr, uses);
+                }
+            }
+            UnaryApplication(f, x) => {
+                self.walk(f, uses);
+                self.walk(x, uses);
+            }
+            BinaryApplication(f, x, y) => {
+                self.walk(f, uses);
+                self.walk(x, uses);
+                self.walk(y, uses);
+            }
+        }
+    }
+}
+
+impl DependencyGraph {
+    pub fn new(expr: &Expression) -> Self {
+        let mut walker = Walker {
+            definitions: vec![],
+            uses: HashMap::new(),
+        };
+        let mut result_uses = vec![];
+        walker.walk(expr, &mut result_uses);
+
+        let Walker {
+            mut definitions,
+            mut uses,
+        } = walker;
+        definitions.sort_by_key(|id| id.id);
+
+        // references to the prelude are not part of the graph
+        let defined = definitions.iter().map(|id| id.id).collect::<HashSet<_>>();
+        for ids in uses.values_mut().chain(std::iter::once(&mut result_uses)) {
+            ids.retain(|id| defined.contains(&id.id));
+            ids.sort_by_key(|id| id.id);
+        }
+
+        DependencyGraph {
+            definitions,
+            uses,
+            result_uses,
+        }
+    }
+
+    // Every definition, ordered by identifier. That's mostly source order,
+    // except that the definitions inside a block come before the assignment
+    // that the block belongs to.
+    pub fn definitions(&self) -> &[RichIdentifier] {
+        &self.definitions
+    }
+
+    // the definitions that this definition refers to
+    pub fn dependencies(&self, id: Identifier) -> &[RichIdentifier] {
+        self.uses.get(&id).map(Vec::as_slice).unwrap_or(&[])
+    }
+
+    // the definitions that refer to this definition
+    pub fn dependents(&self, id: Identifier) -> Vec<&RichIdentifier> {
+        self.definitions
+            .iter()
+            .filter(|definition| self.dependencies(definition.id).iter().any(|x| x.id == id))
+            .collect()
+    }
+
+    // the definitions that the program's result refers to directly
+    pub fn result_dependencies(&self) -> &[RichIdentifier] {
+        &self.result_uses
+    }
+
+    // Definitions that nothing refers to. Bare expression statements are
+    // never used, so they aren't included.
+    pub fn unused(&self) -> Vec<&RichIdentifier> {
+        let used = self
+            .uses
+            .values()
+            .chain(std::iter::once(&self.result_uses))
+            .flatten()
+            .map(|id| id.id)
+            .collect::<HashSet<_>>();
+        self.definitions
+            .iter()
+            .filter(|id| id.name != "_" && !used.contains(&id.id))
+            .collect()
+    }
+
+    // Groups definitions into strongly connected components, ordered so that
+    // every group comes after all of the groups that it depends on. A group
+    // with more than one definition (or a definition that uses itself) is a
+    // set of recursive definitions.
+    pub fn evaluation_order(&self) -> Vec<Vec<&RichIdentifier>> {
+        let mut tarjan = Tarjan {
+            graph: self,
+            next_index: 0,
+            indexes: HashMap::new(),
+            low_links: HashMap::new(),
+            stack: vec![],
+            on_stack: HashSet::new(),
+            components: vec![],
+        };
+        for definition in &self.definitions {
+            if !tarjan.indexes.contains_key(&definition.id) {
+                tarjan.visit(definition);
+            }
+        }
+        tarjan.components
+    }
+
+    // disambiguates shadowed names, so that the second definition of foo is
+    // foo_1, the third is foo_2, and so on
+    fn display_names(&self) -> HashMap<Identifier, String> {
+        let mut counts: HashMap<&str, usize> = HashMap::new();
+        self.definitions
+            .iter()
+            .map(|id| {
+                let count = counts.entry(&id.name).or_insert(0);
+                let name = match *count {
+                    0 => id.name.clone(),
+                    n => format!("{}_{}", id.name, n),
+                };
+                *count += 1;
+                (id.id, name)
+            })
+            .collect()
+    }
+}
+
+struct Tarjan<'a> {
+    graph: &'a DependencyGraph,
+    next_index: usize,
+    indexes: HashMap<Identifier, usize>,
+    low_links: HashMap<Identifier, usize>,
+    stack: Vec<&'a RichIdentifier>,
+    on_stack: HashSet<Identifier>,
+    components: Vec<Vec<&'a RichIdentifier>>,
+}
+
+impl<'a> Tarjan<'a> {
+    fn visit(&mut self, definition: &'a RichIdentifier) {
+        let id = definition.id;
+        self.indexes.insert(id, self.next_index);
+        self.low_links.insert(id, self.next_index);
+        self.next_index += 1;
+        self.stack.push(definition);
+        self.on_stack.insert(id);
+
+        for dependency in self.graph.dependencies(id) {
+            if !self.indexes.contains_key(&dependency.id) {
+                self.visit(dependency);
+                let low_link = self.low_links[&id].min(self.low_links[&dependency.id]);
+                self.low_links.insert(id, low_link);
+            } else if self.on_stack.contains(&dependency.id) {
+                let low_link = self.low_links[&id].min(self.indexes[&dependency.id]);
+                self.low_links.insert(id, low_link);
+            }
+        }
+
+        if self.low_links[&id] == self.indexes[&id] {
+            let mut component = vec![];
+            loop {
+                let member = self.stack.pop().unwrap();
+                self.on_stack.remove(&member.id);
+                component.push(member);
+                if member.id == id {
+                    break;
+                }
+            }
+            component.sort_by_key(|member| member.id);
+            self.components.push(component);
+        }
+    }
+}
+
+impl fmt::Display for DependencyGraph {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names = self.display_names();
+        let write_uses = |f: &mut fmt::Formatter<'_>, uses: &[RichIdentifier]| {
+            write!(f, " ->")?;
+            for id in uses {
+                write!(f, " {}", names[&id.id])?;
+            }
+            writeln!(f)
+        };
+        for definition in &self.definitions {
+            write!(f, "{}", names[&definition.id])?;
+            write_uses(f, self.dependencies(definition.id))?;
+        }
+        write!(f, "<result>")?;
+        write_uses(f, &self.result_uses)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn graph_of(input: &str) -> DependencyGraph {
+        let (expr, _) = crate::parse(input).unwrap();
+        DependencyGraph::new(&expr)
+    }
+
+    fn show_graph(input: &str) -> String {
+        graph_of(input).to_string()
+    }
+
+    fn show_order(graph: &DependencyGraph) -> String {
+        let names = graph.display_names();
+        graph
+            .evaluation_order()
+            .iter()
+            .map(|component| {
+                component
+                    .iter()
+                    .map(|id| names[&id.id].clone())
+                    .collect::<Vec<_>>()
+                    .join(" ")
+            })
+            .collect::<Vec<_>>()
+            .join(", ")
+    }
+
+    #[test]
+    fn test_definitions_and_uses() {
+        k9::snapshot!(
+            show_graph(
+                "
+x = 1
+y = x + z
+z = 2
+x = x + y
+x * 2
+"
+            ),
+            "
+x ->
+y -> x z
+z ->
+x_1 -> x y
+<result> -> x_1
+"
+        );
+    }
+
+    #[test]
+    fn test_nested_blocks_and_parameters() {
+        k9::snapshot!(
+            show_graph(
+                "
+scale = 10
+f (x : n) (y : n) : n =
+  z = x * scale
+  z + 1
+f = 1 f 2
+f
+"
+            ),
+            "
+scale ->
+z -> scale x
+f -> z
+x ->
+y ->
+f_1 -> f
+<result> -> f_1
+"
+        );
+    }
+
+    #[test]
+    fn test_unused() {
+        let graph = graph_of(
+            "
+a = 1
+b = a + 1
+f (x : n) (y : n) : n = x
+1 + 2
+f 1 2
+",
+        );
+        let unused = graph
+            .unused()
+            .iter()
+            .map(|id| id.name.as_str())
+            .collect::<Vec<_>>();
+        k9::snapshot!(unused.join(" "), "b y");
+    }
+
+    #[test]
+    fn test_dependents() {
+        let graph = graph_of(
+            "
+a = 1
+b = a + 1
+c = a + b
+c
+",
+        );
+        let a = &graph.definitions()[0];
+        let dependents = graph
+            .dependents(a.id)
+            .iter()
+            .map(|id| id.name.as_str())
+            .collect::<Vec<_>>();
+        k9::snapshot!(dependents.join(" "), "b c");
+    }
+
+    #[test]
+    fn test_evaluation_order() {
+        k9::snapshot!(
+            show_order(&graph_of(
+                "
+c = a + b
+b = a + 1
+a = 1
+c
+"
+            )),
+            "a, b, c"
+        );
+        k9::snapshot!(
+            show_order(&graph_of(
+                "
+even (x : n) : n =
+  if x
+    odd x - 1
+  else
+    1
+odd (x : n) : n =
+  if x
+    even x - 1
+  else
+    0
+y = even 10
+y
+"
+            )),
+            "x, x_1, even odd, y"
+        );
+    }
+}
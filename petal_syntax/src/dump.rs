@@ -0,0 +1,220 @@
+use crate::statement::{Block, Statement};
+use crate::token::Token;
+use std::fmt::Display;
+use std::str::FromStr;
+
+// A point in the parsing pipeline whose output we can look at. See terms.rs
+// for what each kind of term is for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stage {
+    Tokens,
+    SemiSoupy,
+    Soupy,
+    Soupless,
+    Grouped,
+    Expression,
+}
+
+pub const STAGES: &[&str] = &[
+    "tokens",
+    "semi-soupy",
+    "soupy",
+    "soupless",
+    "grouped",
+    "expression",
+];
+
+impl FromStr for Stage {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        use Stage::*;
+        match s {
+            "tokens" => Ok(Tokens),
+            "semi-soupy" => Ok(SemiSoupy),
+            "soupy" => Ok(Soupy),
+            "soupless" => Ok(Soupless),
+            "grouped" => Ok(Grouped),
+            "expression" => Ok(Expression),
+            _ => Err(format!(
+                "unknown stage {}, expected one of: {}",
+                s,
+                STAGES.join(", ")
+            )),
+        }
+    }
+}
+
+// Runs the parser up to the given stage and shows what came out of it, or the
+// error that stopped it from getting that far.
+pub fn dump(input: &str, stage: Stage) -> Result<String, String> {
+    let tokens = crate::tokenizer::tokenize(input).map_err(|error| error.to_string())?;
+    if stage == Stage::Tokens {
+        let mut output = String::new();
+        for token in tokens {
+            output += &token.token.to_string();
+            output.push(if token.token == Token::Newline {
+                '\n'
+            } else {
+                ' '
+            });
+        }
+        return Ok(output);
+    }
+    let statements =
+        crate::statement_parser::parse_tokens(tokens).map_err(|error| error.to_string())?;
+    if stage == Stage::SemiSoupy {
+        return Ok(show_block(&statements));
+    }
+    let statements = crate::semicolons::rewrite(statements);
+    if stage == Stage::Soupy {
+        return Ok(show_block(&statements));
+    }
+    let statements = crate::op_splitter::rewrite(statements);
+    if stage == Stage::Soupless {
+        return Ok(show_block(&statements));
+    }
+    let statements = crate::coefficient_grouper::rewrite(statements);
+    if stage == Stage::Grouped {
+        return Ok(show_block(&statements));
+    }
+    match crate::pos_parser::just_parse(statements) {
+        Ok((expr, pos)) => Ok(format!("{}:{}\n", pos, expr)),
+        Err(error) => Err(error.to_string()),
+    }
+}
+
+fn show_block<T: Display>(block: &Block<T>) -> String {
+    let mut output = String::new();
+    write_block(&mut output, block, 0);
+    output
+}
+
+fn write_block<T: Display>(output: &mut String, block: &Block<T>, depth: usize) {
+    for statement in block {
+        write_statement(output, statement, depth);
+    }
+}
+
+fn write_line(output: &mut String, depth: usize, text: &str) {
+    output.push_str(&"  ".repeat(depth));
+    output.push_str(text);
+    output.push('\n');
+}
+
+fn show_terms<T: Display>(terms: &[T]) -> String {
+    terms
+        .iter()
+        .map(|term| term.to_string())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
+
+fn head(name: &impl Display, signature: &Option<crate::statement::Signature>) -> String {
+    match signature {
+        Some(signature) => format!("{} {} =", name, signature),
+        None => format!("{} =", name),
+    }
+}
+
+fn write_statement<T: Display>(output: &mut String, statement: &Statement<T>, depth: usize) {
+    match statement {
+        Statement::SimpleAssignment(name, signature, terms) => {
+            let line = format!("{} {}", head(name, signature), show_terms(terms));
+            write_line(output, depth, &line);
+        }
+        Statement::CompoundAssignment(name, signature, block) => {
+            write_line(output, depth, &head(name, signature));
+            write_block(output, block, depth + 1);
+        }
+        Statement::Expression(terms) => write_line(output, depth, &show_terms(terms)),
+        Statement::Declaration(name, signature) => {
+            write_line(output, depth, &format!("{} {}", name, signature))
+        }
+        Statement::Destructuring(names, terms) => {
+            let line = format!("({}) = {}", show_terms(names), show_terms(terms));
+            write_line(output, depth, &line);
+        }
+        Statement::Conditional(terms, consequent, _, alternative) => {
+            write_line(output, depth, &format!("if {}", show_terms(terms)));
+            write_block(output, consequent, depth + 1);
+            write_line(output, depth, "else");
+            write_block(output, alternative, depth + 1);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test(input: &str, stage: &str) -> String {
+        match dump(input, stage.parse().unwrap()) {
+            Ok(output) => output.trim_end().to_string(),
+            Err(error) => format!("error: {}", error),
+        }
+    }
+
+    #[test]
+    fn test_stages() {
+        let input = "x = 1\nf (y : n) : n = y+-x;2x\nf 3";
+        k9::snapshot!(
+            test(input, "tokens"),
+            "
+x ␠ = ␠ 1 ␤
+f ␠ ( y ␠ : ␠ n ) ␠ : ␠ n ␠ = ␠ y +- x ; 2 x ␤
+f ␠ 3 ␤
+"
+        );
+        k9::snapshot!(
+            test(input, "semi-soupy"),
+            "
+x = 1
+f (y : n) : n = y +- x ; 2 x
+f ␠ 3
+"
+        );
+        k9::snapshot!(
+            test(input, "soupy"),
+            "
+x = 1
+f (y : n) : n = (y +- x) (2 x)
+f ␠ 3
+"
+        );
+        k9::snapshot!(
+            test(input, "soupless"),
+            "
+x = 1
+f (y : n) : n = (y + - x) (2 x)
+f ␠ 3
+"
+        );
+        k9::snapshot!(
+            test(input, "grouped"),
+            "
+x = 1
+f (y : n) : n = (y + (<scale -1> x)) ((<scale 2> x))
+f 3
+"
+        );
+        k9::snapshot!(
+            test(input, "expression"),
+            "n:(let ((x 1) (f (fn (y) (<tuple> (+ y ((<scale> -1) x)) ((<scale> 2) x))))) (f 3))"
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        k9::snapshot!(
+            "lexemes".parse::<Stage>().unwrap_err(),
+            "unknown stage lexemes, expected one of: tokens, semi-soupy, soupy, soupless, grouped, expression"
+        );
+        k9::snapshot!(
+            test("x = zed", "expression"),
+            "error: x (line 1): zed is not defined"
+        );
+        // everything before the pos parser still works
+        k9::snapshot!(test("x = zed", "grouped"), "x = zed");
+    }
+}
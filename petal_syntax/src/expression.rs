@@ -1,3 +1,4 @@
+use crate::pos_parser::PartOfSpeech;
 use std::collections::HashMap;
 use std::fmt;
 use std::hash::{Hash, Hasher};
@@ -5,14 +6,22 @@ use std::hash::{Hash, Hasher};
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum Expression {
     Compound(HashMap<RichIdentifier, Expression>, Box<Expression>),
+    // the part of speech of the definition itself, not of its body
+    Lambda(
+        Vec<(RichIdentifier, PartOfSpeech)>,
+        Box<Expression>,
+        PartOfSpeech,
+    ),
+    Conditional(Box<Expression>, Box<Expression>, Box<Expression>),
     Implicit(Builtin),
     Identifier(RichIdentifier),
     NumericLiteral(String),
     Parens(Box<Expression>),
-    // TODO: currently tuples and brackets store their elements in reverse
-    // order, which is a dumb performance hack.
     Tuple(Vec<Expression>),
     Brackets(Vec<Expression>),
+    // a tuple, the index of one of its elements, and how many elements it has
+    // to have. This is what names bound by a destructuring assignment refer to.
+    Element(Box<Expression>, usize, usize),
     UnaryApplication(Box<Expression>, Box<Expression>),
     BinaryApplication(Box<Expression>, Box<Expression>, Box<Expression>),
 }
@@ -99,13 +108,23 @@ impl fmt::Display for Expression {
                 }
                 write!(f, ") {})", expr)
             }
+            Lambda(params, body, _) => {
+                write!(f, "(fn (")?;
+                for (i, (param, _)) in params.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, " ")?;
+                    }
+                    write!(f, "{}", param)?;
+                }
+                write!(f, ") {})", body)
+            }
             Identifier(rich_id) => write!(f, "{}", rich_id),
             NumericLiteral(num) => write!(f, "{}", num),
             Implicit(builtin) => write!(f, "<{}>", builtin),
             Parens(expr) => write!(f, "{}", expr),
             Brackets(exprs) => {
                 write!(f, "[")?;
-                for (i, expr) in exprs.iter().rev().enumerate() {
+                for (i, expr) in exprs.iter().enumerate() {
                     if i != 0 {
                         write!(f, " ")?;
                     }
@@ -119,12 +138,18 @@ impl fmt::Display for Expression {
                     write!(f, "<unit>")
                 } else {
                     write!(f, "(<tuple>")?;
-                    for expr in exprs.iter().rev() {
+                    for expr in exprs {
                         write!(f, " {}", expr)?;
                     }
                     write!(f, ")")
                 }
             }
+            Element(tuple, index, length) => {
+                write!(f, "(<element {}/{}> {})", index, length, tuple)
+            }
+            Conditional(condition, consequent, alternative) => {
+                write!(f, "(if {} {} {})", condition, consequent, alternative)
+            }
             UnaryApplication(func, expr) => {
                 write!(f, "({} {})", func, expr)
             }
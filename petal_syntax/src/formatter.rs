@@ -0,0 +1,758 @@
+use crate::pos_parser::ParseError;
+use crate::statement::{Block, Signature, Statement};
+use crate::terms::Term;
+
+// Prints a program in a canonical style:
+//
+// - every term is separated by exactly one space, except for coefficients,
+//   which are attached to the thing they scale: 2x, -(f y)
+// - arrays of arrays are written with semicolons: [1 2; 3 4]
+// - semicolons in parens are expanded into nested parens
+// - blocks are indented by two spaces, and runs of blank lines between
+//   statements become a single blank line
+// - expressions that continue onto more lines inside of parens or brackets
+//   break in the same places, and the lines inside are indented by two more
+//   spaces
+// - comments on their own line are indented like the statement after them,
+//   and comments at the end of a line stay at the end of the line
+//
+// The output always parses to the same thing as the input, since it's printed
+// from the terms after operators have been split and negation has been
+// resolved. A program that doesn't tokenize or parse into statements can't be
+// formatted at all.
+pub fn format(input: &str) -> Result<String, ParseError> {
+    let statements = crate::statements(crate::tokenizer::tokenize(input)?)?;
+    let mut output = Output {
+        text: String::new(),
+        comments: comments(input),
+    };
+    block(&mut output, &statements, 0);
+    output.comments_before(u32::MAX, 0);
+    Ok(output.text)
+}
+
+// Whether two programs mean the same thing: either they parse to the same
+// expression, or neither of them parses.
+pub fn same_meaning(before: &str, after: &str) -> bool {
+    match (crate::parse(before), crate::parse(after)) {
+        (Ok(before), Ok(after)) => before == after,
+        (Err(_), Err(_)) => true,
+        _ => false,
+    }
+}
+
+const INDENT: &str = "  ";
+
+// Comments don't make it through the parser, so we find them in the source and
+// put them back wherever they were relative to the statements around them.
+struct Comment {
+    line: u32,
+    text: String,
+}
+
+// the comments of the source, in order
+fn comments(input: &str) -> Vec<Comment> {
+    input
+        .lines()
+        .zip(1..)
+        .filter_map(|(line, number)| {
+            let start = line.find('#')?;
+            Some(Comment {
+                line: number,
+                text: line[start..].trim_end().to_string(),
+            })
+        })
+        .collect()
+}
+
+struct Output {
+    text: String,
+    // the comments that haven't been written yet
+    comments: Vec<Comment>,
+}
+
+impl Output {
+    // writes every comment that comes before the given line on a line of its
+    // own
+    fn comments_before(&mut self, line: u32, depth: usize) {
+        let count = self
+            .comments
+            .iter()
+            .take_while(|comment| comment.line < line)
+            .count();
+        for comment in self.comments.drain(..count).collect::<Vec<_>>() {
+            self.line(depth, &comment.text, None);
+        }
+    }
+
+    // Writes a line of output that came from the given lines of source, along
+    // with any comments on those lines.
+    fn line(&mut self, depth: usize, text: &str, source: Option<(u32, u32)>) {
+        let mut text = text.to_string();
+        if let Some((first, last)) = source {
+            self.comments_before(first, depth);
+            let count = self
+                .comments
+                .iter()
+                .take_while(|comment| comment.line <= last)
+                .count();
+            for comment in self.comments.drain(..count) {
+                text += " ";
+                text += &comment.text;
+            }
+        }
+        self.text.push_str(&INDENT.repeat(depth));
+        self.text.push_str(&text);
+        self.text.push('\n');
+    }
+
+    // Writes the lines of a statement, which ends on the given line of source.
+    fn lines(&mut self, depth: usize, lines: Lines, last: u32) {
+        let count = lines.lines.len();
+        for (i, line) in lines.lines.into_iter().enumerate() {
+            let end = if i + 1 == count { last } else { line.source };
+            self.comments_before(line.source, depth + line.inside);
+            self.line(depth + line.depth, &line.text, Some((line.source, end)));
+        }
+    }
+}
+
+// The text of a statement, broken into lines wherever the source was, as long
+// as the break is inside of parens or brackets.
+#[derive(Default)]
+struct Lines {
+    lines: Vec<Line>,
+    // the index of the line that each delimiter that's still open is on
+    open: Vec<usize>,
+    // whether the next text is separated by a space from the text before it
+    space: bool,
+}
+
+struct Line {
+    // the line of source that it starts on
+    source: u32,
+    // how many levels it's indented past the statement, and how many the
+    // comments above it are, which are inside of any delimiters it closes
+    depth: usize,
+    inside: usize,
+    text: String,
+    // whether it starts with closing delimiters, and nothing else so far
+    closing: bool,
+}
+
+impl Lines {
+    // one level for every line above with delimiters that are still open
+    fn levels(&self) -> usize {
+        let mut open = self.open.clone();
+        open.dedup();
+        open.len()
+    }
+
+    fn push(&mut self, text: &str, source: Option<u32>) {
+        let below = match (self.lines.last(), source) {
+            (None, _) => true,
+            (Some(line), Some(source)) => source > line.source && !self.open.is_empty(),
+            (Some(_), None) => false,
+        };
+        if below {
+            self.lines.push(Line {
+                source: source.unwrap(),
+                depth: self.levels(),
+                inside: self.levels(),
+                text: String::new(),
+                closing: true,
+            });
+            self.space = false;
+        }
+        let line = self.lines.last_mut().unwrap();
+        if self.space {
+            line.text.push(' ');
+        }
+        line.text.push_str(text);
+    }
+
+    fn word(&mut self, text: &str, source: u32) {
+        self.push(text, Some(source));
+        self.lines.last_mut().unwrap().closing = false;
+        self.space = true;
+    }
+
+    // text that's attached to whatever comes after it
+    fn prefix(&mut self, text: &str, source: u32) {
+        self.word(text, source);
+        self.space = false;
+    }
+
+    fn open(&mut self, text: &str, source: u32) {
+        self.prefix(text, source);
+        self.open.push(self.lines.len() - 1);
+    }
+
+    // A line that starts by closing delimiters lines up with the line that
+    // opened them.
+    fn close(&mut self, text: &str, source: u32) {
+        self.space = false;
+        self.push(text, Some(source));
+        self.open.pop();
+        let levels = self.levels();
+        let line = self.lines.last_mut().unwrap();
+        if line.closing {
+            line.depth = levels;
+        }
+        self.space = true;
+    }
+
+    // text that stays on the line of whatever comes before it
+    fn separator(&mut self, text: &str) {
+        self.space = false;
+        self.push(text, None);
+        self.lines.last_mut().unwrap().closing = false;
+        self.space = true;
+    }
+}
+
+fn block(output: &mut Output, statements: &Block<Term>, depth: usize) {
+    let mut previous_line = None;
+    for statement in statements {
+        let lines = lines_of_statement(statement);
+        if let (Some(previous), Some((first, _))) = (previous_line, lines) {
+            if first > previous + 1 {
+                output.text.push('\n');
+            }
+        }
+        previous_line = lines.map(|(_, last)| last);
+        self::statement(output, statement, depth);
+    }
+}
+
+fn statement(output: &mut Output, statement: &Statement<Term>, depth: usize) {
+    // every statement has at least one name in it
+    let (first, last) = lines_of_statement(statement).unwrap();
+    match statement {
+        Statement::SimpleAssignment(name, signature, body) => {
+            let mut lines = Lines::default();
+            let head = format!("{}{} =", name, self::signature(signature));
+            lines.word(&head, name.location.line);
+            terms(&mut lines, body);
+            output.lines(depth, lines, last);
+        }
+        Statement::CompoundAssignment(name, signature, body) => {
+            let head = format!("{}{} =", name, self::signature(signature));
+            let line = name.location.line;
+            output.line(depth, &head, Some((line, line)));
+            block(output, body, depth + 1);
+        }
+        Statement::Expression(body) => {
+            let mut lines = Lines::default();
+            terms(&mut lines, body);
+            output.lines(depth, lines, last);
+        }
+        Statement::Declaration(name, signature) => output.line(
+            depth,
+            &format!("{} {}", name, signature),
+            Some((first, last)),
+        ),
+        Statement::Destructuring(names, body) => {
+            let mut lines = Lines::default();
+            let names = names.iter().map(ToString::to_string).collect::<Vec<_>>();
+            lines.word(&format!("({}) =", names.join(" ")), first);
+            terms(&mut lines, body);
+            output.lines(depth, lines, last);
+        }
+        Statement::Conditional(condition, consequent, otherwise, alternative) => {
+            let mut head = Lines::default();
+            head.word("if", first);
+            let otherwise = otherwise.line;
+            conditional(
+                output,
+                head,
+                condition,
+                consequent,
+                otherwise,
+                alternative,
+                depth,
+            )
+        }
+    }
+}
+
+// Writes a conditional after its keyword, which is already in the head. An
+// else with nothing but another conditional in it is written as an else if,
+// on the line of the else.
+fn conditional(
+    output: &mut Output,
+    mut head: Lines,
+    condition: &[Term],
+    consequent: &Block<Term>,
+    otherwise: u32,
+    alternative: &Block<Term>,
+    depth: usize,
+) {
+    let (_, last) = lines_of_terms(condition).unwrap();
+    terms(&mut head, condition);
+    output.lines(depth, head, last);
+    block(output, consequent, depth + 1);
+    match alternative.as_slice() {
+        [Statement::Conditional(condition, consequent, next, alternative)] => {
+            let mut head = Lines::default();
+            head.word("else if", otherwise);
+            let next = next.line;
+            conditional(
+                output,
+                head,
+                condition,
+                consequent,
+                next,
+                alternative,
+                depth,
+            )
+        }
+        _ => {
+            output.line(depth, "else", Some((otherwise, otherwise)));
+            block(output, alternative, depth + 1);
+        }
+    }
+}
+
+fn signature(signature: &Option<Signature>) -> String {
+    match signature {
+        Some(signature) => format!(" {}", signature),
+        None => String::new(),
+    }
+}
+
+fn terms(lines: &mut Lines, terms: &[Term]) {
+    for term in terms {
+        self::term(lines, term);
+    }
+}
+
+fn term(lines: &mut Lines, term: &Term) {
+    match term {
+        Term::Identifier(name) | Term::NumericLiteral(name) | Term::UnknownOperator(name, _) => {
+            lines.word(&name.name, name.location.line)
+        }
+        // these only appear at the start of parens that the coefficient
+        // grouper added, which are handled below
+        Term::Coefficient(_, token) => lines.word(&token.name, token.location.line),
+        Term::Parens(inner, delimiters) => match inner.as_slice() {
+            [Term::Coefficient(_, token), scaled] => {
+                lines.prefix(&token.name, token.location.line);
+                self::term(lines, scaled);
+            }
+            _ => {
+                lines.open("(", delimiters.open.line);
+                terms(lines, inner);
+                lines.close(")", delimiters.close.line);
+            }
+        },
+        Term::Brackets(inner, delimiters) => {
+            lines.open("[", delimiters.open.line);
+            rows(lines, inner, semicolon_depth(inner));
+            lines.close("]", delimiters.close.line);
+        }
+    }
+}
+
+// How many levels of nested brackets we can write with semicolons. That's only
+// possible when there are at least two rows, and none of them are empty.
+fn semicolon_depth(terms: &[Term]) -> usize {
+    if terms.len() < 2 {
+        return 0;
+    }
+    let mut depth = usize::MAX;
+    for term in terms {
+        match term {
+            Term::Brackets(inner, _) if !inner.is_empty() => {
+                depth = depth.min(semicolon_depth(inner));
+            }
+            _ => return 0,
+        }
+    }
+    depth + 1
+}
+
+fn rows(lines: &mut Lines, terms: &[Term], depth: usize) {
+    if depth == 0 {
+        return self::terms(lines, terms);
+    }
+    let separator = ";".repeat(depth);
+    for (i, term) in terms.iter().enumerate() {
+        if i > 0 {
+            lines.separator(&separator);
+        }
+        match term {
+            Term::Brackets(inner, _) => rows(lines, inner, depth - 1),
+            _ => unreachable!(),
+        }
+    }
+}
+
+// The first and last lines of the statement that we know about. Names,
+// numbers, and delimiters keep track of where they came from.
+fn lines_of_statement(statement: &Statement<Term>) -> Option<(u32, u32)> {
+    lines_of(|mut see| visit_statement(statement, &mut see))
+}
+
+fn lines_of_terms(terms: &[Term]) -> Option<(u32, u32)> {
+    lines_of(|mut see| visit_terms(terms, &mut see))
+}
+
+fn lines_of(visit: impl FnOnce(&mut dyn FnMut(u32))) -> Option<(u32, u32)> {
+    let mut lines = None;
+    let mut see = |line: u32| {
+        lines = Some(match lines {
+            None => (line, line),
+            Some((first, last)) => (u32::min(first, line), u32::max(last, line)),
+        })
+    };
+    visit(&mut see);
+    lines
+}
+
+fn visit_statement(statement: &Statement<Term>, see: &mut impl FnMut(u32)) {
+    match statement {
+        Statement::SimpleAssignment(name, _, body) => {
+            see(name.location.line);
+            visit_terms(body, see);
+        }
+        Statement::CompoundAssignment(name, _, body) => {
+            see(name.location.line);
+            body.iter()
+                .for_each(|statement| visit_statement(statement, see));
+        }
+        Statement::Expression(body) => visit_terms(body, see),
+        Statement::Declaration(name, _) => see(name.location.line),
+        Statement::Destructuring(names, body) => {
+            names.iter().for_each(|name| see(name.location.line));
+            visit_terms(body, see);
+        }
+        Statement::Conditional(condition, consequent, otherwise, alternative) => {
+            visit_terms(condition, see);
+            consequent
+                .iter()
+                .for_each(|statement| visit_statement(statement, see));
+            see(otherwise.line);
+            alternative
+                .iter()
+                .for_each(|statement| visit_statement(statement, see));
+        }
+    }
+}
+
+fn visit_terms(terms: &[Term], see: &mut impl FnMut(u32)) {
+    for term in terms {
+        match term {
+            Term::Identifier(name)
+            | Term::NumericLiteral(name)
+            | Term::Coefficient(_, name)
+            | Term::UnknownOperator(name, _) => see(name.location.line),
+            Term::Parens(inner, delimiters) | Term::Brackets(inner, delimiters) => {
+                see(delimiters.open.line);
+                visit_terms(inner, see);
+                see(delimiters.close.line);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // every test also checks that formatting doesn't change the meaning, and
+    // that formatting is idempotent
+    fn test(input: &str) -> String {
+        let output = format(input).unwrap();
+        assert!(same_meaning(input, &output), "{:?} => {:?}", input, output);
+        assert_eq!(format(&output).unwrap(), output);
+        output.trim_end().to_string()
+    }
+
+    #[test]
+    fn test_spacing() {
+        k9::snapshot!(test("x=1+2"), "x = 1 + 2");
+        k9::snapshot!(test("x   =  1   +2"), "x = 1 + 2");
+        k9::snapshot!(test("(neg  sign)1"), "(neg sign) 1");
+        k9::snapshot!(test("fold+[1 2]"), "fold + [1 2]");
+        k9::snapshot!(test("1+-2"), "1 + -2");
+        k9::snapshot!(test("( a  b )=(1 2)\na"), "(a b) = (1 2)\na");
+        k9::snapshot!(
+            test("<+> :v2\n<+> = +\n1<+>2"),
+            "
+<+> : v2
+<+> = +
+1 <+> 2
+"
+        );
+    }
+
+    #[test]
+    fn test_negation() {
+        k9::snapshot!(
+            test("x = 1\nx-x"),
+            "
+x = 1
+x - x
+"
+        );
+        k9::snapshot!(
+            test("x = 1\nx -x"),
+            "
+x = 1
+x -x
+"
+        );
+        k9::snapshot!(
+            test("x = 1\nx--x"),
+            "
+x = 1
+x - -x
+"
+        );
+        k9::snapshot!(
+            test("x = 1\n1-x"),
+            "
+x = 1
+1 -x
+"
+        );
+        k9::snapshot!(
+            test("x = 1\n2x + -(x)"),
+            "
+x = 1
+2x + -(x)
+"
+        );
+    }
+
+    #[test]
+    fn test_operator_soup() {
+        k9::snapshot!(
+            test("x = 1\nx+-x"),
+            "
+x = 1
+x + -x
+"
+        );
+        k9::snapshot!(
+            test("x = 1\nx+- x"),
+            "
+x = 1
+x + - x
+"
+        );
+    }
+
+    #[test]
+    fn test_semicolons() {
+        k9::snapshot!(test("[[1 2] [3 4]]"), "[1 2; 3 4]");
+        k9::snapshot!(test("[1 2;3 4;;5 6;7 8]"), "[1 2; 3 4;; 5 6; 7 8]");
+        k9::snapshot!(test("[[[1 2] [3 4]] [5 6]]"), "[[1 2] [3 4]; 5 6]");
+        k9::snapshot!(test("[[1]]"), "[[1]]");
+        k9::snapshot!(test("[[] [1]]"), "[[] [1]]");
+        k9::snapshot!(test("(1 + 2; neg 3)"), "((1 + 2) (neg 3))");
+    }
+
+    #[test]
+    fn test_comments() {
+        k9::snapshot!(
+            test(
+                "
+# doubles things
+double (x:n):n=x*2 # like this
+
+f = # not a verb
+# the first step
+    y = 1
+    if y>0 # always
+        y
+    else # never
+        0
+f   # the end
+"
+            ),
+            "
+# doubles things
+double (x : n) : n = x * 2 # like this
+
+f = # not a verb
+  # the first step
+  y = 1
+  if y > 0 # always
+    y
+  else # never
+    0
+f # the end
+"
+        );
+        k9::snapshot!(
+            test("x = [1 2 # one\n     3]\nx\n# done"),
+            "
+x = [1 2 # one
+  3]
+x
+# done
+"
+        );
+    }
+
+    #[test]
+    fn test_else_comments() {
+        k9::snapshot!(
+            test("x = 1\nif x # first\n  neg x\nelse # otherwise\n  x"),
+            "
+x = 1
+if x # first
+  neg x
+else # otherwise
+  x
+"
+        );
+        k9::snapshot!(
+            test("x = 1\nif x\n  neg x\nelse if x > 1 # more\n  0\nelse # less\n  x"),
+            "
+x = 1
+if x
+  neg x
+else if x > 1 # more
+  0
+else # less
+  x
+"
+        );
+        // an else that only has a conditional in it becomes an else if
+        k9::snapshot!(
+            test("x = 1\nif x\n  neg x\nelse # nested\n  if x > 1\n    0\n  else\n    x"),
+            "
+x = 1
+if x
+  neg x
+else if x > 1 # nested
+  0
+else
+  x
+"
+        );
+    }
+
+    #[test]
+    fn test_continuation_lines() {
+        k9::snapshot!(
+            test("x = [\n  1 2 # first row\n  3 4 # second row\n] # done\nx"),
+            "
+x = [
+  1 2 # first row
+  3 4 # second row
+] # done
+x
+"
+        );
+        k9::snapshot!(
+            test("x = [1 2\n]\nx"),
+            "
+x = [1 2
+]
+x
+"
+        );
+        k9::snapshot!(
+            test("identity = [\n1 0 0;\n      0 1 0;\n  0 0 1\n  ]\nidentity"),
+            "
+identity = [
+  1 0 0;
+  0 1 0;
+  0 0 1
+]
+identity
+"
+        );
+        k9::snapshot!(
+            test("x = [[1 2]\n[3 4] # rows\n# between\n]\nx"),
+            "
+x = [1 2;
+  3 4 # rows
+  # between
+]
+x
+"
+        );
+        k9::snapshot!(
+            test("f = neg\nf [\n(1\n2)\n(3 4)]"),
+            "
+f = neg
+f [
+  (1
+    2)
+  (3 4)]
+"
+        );
+        k9::snapshot!(
+            test("x = (1 + 2;\nneg 3)\nx"),
+            "
+x = ((1 + 2)
+  (neg 3))
+x
+"
+        );
+        k9::snapshot!(
+            test("x = 1\ny = 2(x +\n  1)\ny"),
+            "
+x = 1
+y = 2(x +
+  1)
+y
+"
+        );
+    }
+
+    #[test]
+    fn test_blocks() {
+        k9::snapshot!(
+            test(
+                "
+double (x:n):n=x*2
+
+
+sgn (x : n) : n =
+    if x < 0
+          neg 1
+    else
+          if x == 0
+              0
+          else
+              1
+f =
+ y = 1
+
+ y + 1
+sgn double f
+"
+            ),
+            "
+double (x : n) : n = x * 2
+
+sgn (x : n) : n =
+  if x < 0
+    neg 1
+  else if x == 0
+    0
+  else
+    1
+f =
+  y = 1
+
+  y + 1
+sgn double f
+"
+        );
+    }
+
+    #[test]
+    fn test_invalid_syntax() {
+        k9::snapshot!(
+            format("x = 1\ny = (x +\n").unwrap_err().to_string(),
+            "invalid syntax on line 2"
+        );
+    }
+}
@@ -0,0 +1,156 @@
+use crate::expression::Builtin;
+use crate::pos_parser::{Arity, ParseError, PartOfSpeech};
+use crate::token::Token;
+use std::ops::Range;
+
+// A span of source and what it means, for syntax highlighting. Names that
+// failed to parse have no part of speech, and neither does the space between
+// two things that were implicitly combined.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Highlight {
+    pub span: Range<usize>,
+    pub pos: Option<PartOfSpeech>,
+    pub implicit: Option<Builtin>,
+}
+
+// Every name, numeric literal, and implicit builtin in the program, in order.
+// Anything else -- whitespace, parens, equal signs -- is left out. Programs
+// that fail to parse still highlight everything that resolved, as long as
+// they're made of statements at all.
+pub fn highlight(input: &str) -> Result<Vec<Highlight>, ParseError> {
+    let tokens = crate::tokenizer::tokenize(input)?;
+    let (result, symbols) = crate::parse_with_symbols(input);
+    if let Err(error @ ParseError::InvalidSyntax(_)) = result {
+        return Err(error);
+    }
+    let mut highlights = vec![];
+    for symbol in symbols.definitions.iter().chain(&symbols.references) {
+        let start = symbol.location.offset;
+        highlights.push(Highlight {
+            span: start..start + symbol.name.len(),
+            pos: symbol.pos,
+            implicit: None,
+        });
+    }
+    let mut coefficients = vec![];
+    for implicit in symbols.implicit {
+        let pos = match implicit.builtin {
+            Builtin::Scale => {
+                coefficients.push(implicit.extent.start);
+                Some(PartOfSpeech::Verb(Arity::Unary))
+            }
+            _ => None,
+        };
+        highlights.push(Highlight {
+            span: implicit.extent,
+            pos,
+            implicit: Some(implicit.builtin),
+        });
+    }
+    // the parser doesn't keep track of literals, but they're always nouns
+    // unless they're coefficients
+    for token in tokens {
+        if let Token::NumericLiteral(literal) = token.token {
+            let start = token.location.offset;
+            if !coefficients.contains(&start) {
+                highlights.push(Highlight {
+                    span: start..start + literal.len(),
+                    pos: Some(PartOfSpeech::Noun),
+                    implicit: None,
+                });
+            }
+        }
+    }
+    highlights.sort_by_key(|highlight| (highlight.span.start, highlight.span.end));
+    Ok(highlights)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test(input: &str) -> String {
+        let highlights = highlight(input)
+            .unwrap()
+            .into_iter()
+            .map(|highlight| {
+                let mut result = format!("'{}'", &input[highlight.span]);
+                if let Some(pos) = highlight.pos {
+                    result += &format!(" {}", pos);
+                }
+                if let Some(builtin) = highlight.implicit {
+                    result += &format!(" <{:?}>", builtin);
+                }
+                result
+            })
+            .collect::<Vec<_>>();
+        format!("\n{}\n", highlights.join("\n"))
+    }
+
+    #[test]
+    fn test_parts_of_speech() {
+        k9::snapshot!(
+            test("x = 1 + 2\nfold + x"),
+            "
+'x' n
+'1' n
+'+' v2
+'2' n
+'fold' a1
+'+' v2
+'x' n
+"
+        );
+    }
+
+    #[test]
+    fn test_implicit_builtins() {
+        k9::snapshot!(
+            test("(neg sign) 1"),
+            "
+'neg' v1
+' ' <Compose>
+'sign' v1
+'1' n
+"
+        );
+        k9::snapshot!(
+            test("(* 2) 3"),
+            "
+'*' v2
+' ' <PartialApplicationRight>
+'2' n
+'3' n
+"
+        );
+        k9::snapshot!(
+            test("x = 3\n2x + -x"),
+            "
+'x' n
+'3' n
+'2' v1 <Scale>
+'x' n
+'+' v2
+'-' v1 <Scale>
+'x' n
+"
+        );
+    }
+
+    #[test]
+    fn test_failures() {
+        // expressions parse from right to left, so this never gets to x
+        k9::snapshot!(
+            test("x = [+]\nx + y"),
+            "
+'x'
+'+' v2
+"
+        );
+        // but there's nothing to highlight without statements
+        k9::snapshot!(
+            highlight("x = 1\ny = (x +\n").unwrap_err(),
+            "InvalidSyntax(Location { offset: 6, line: 2 })"
+        );
+    }
+}
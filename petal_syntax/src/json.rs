@@ -0,0 +1,268 @@
+use crate::expression::{Builtin, Expression, Identifier, RichIdentifier};
+use crate::pos_parser::{Arity, ParseError, PartOfSpeech, Symbol};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+
+// Parses a program into JSON, for tools that aren't written in Rust. The
+// schema is documented in the README.
+pub fn parse_to_json(input: &str) -> Result<Value, ParseError> {
+    let (result, symbols) = crate::parse_with_symbols(input);
+    let (expr, pos) = result?;
+    let spans = symbols
+        .definitions
+        .iter()
+        .map(|symbol| (symbol.id, span(symbol)))
+        .collect::<HashMap<_, _>>();
+    let references = symbols
+        .references
+        .iter()
+        .map(|symbol| json!({"id": symbol.id, "name": symbol.name, "span": span(symbol)}))
+        .collect::<Vec<_>>();
+    Ok(json!({
+        "pos": part_of_speech(pos),
+        "expression": expression(&expr, &spans),
+        "references": references,
+    }))
+}
+
+// Lines start at 1, and offsets are bytes.
+fn span(symbol: &Symbol) -> Value {
+    json!({
+        "start": symbol.location.offset,
+        "end": symbol.location.offset + symbol.name.len(),
+        "line": symbol.location.line,
+    })
+}
+
+fn arity(arity: Arity) -> u8 {
+    match arity {
+        Arity::Unary => 1,
+        Arity::Binary => 2,
+    }
+}
+
+pub fn part_of_speech(pos: PartOfSpeech) -> Value {
+    match pos {
+        PartOfSpeech::Noun => json!({"part": "noun"}),
+        PartOfSpeech::Verb(a) => json!({"part": "verb", "arity": arity(a)}),
+        PartOfSpeech::Adverb(a, result, _) => {
+            json!({"part": "adverb", "arity": arity(a), "result_arity": arity(result)})
+        }
+    }
+}
+
+pub fn builtin(builtin: Builtin) -> Value {
+    let name = match builtin {
+        Builtin::Scale => "scale",
+        Builtin::PartialApplicationLeft => "partial-left",
+        Builtin::PartialApplicationRight => "partial-right",
+        Builtin::Compose => "compose",
+        Builtin::ComposeLeft => "compose-left",
+        Builtin::ComposeRight => "compose-right",
+    };
+    json!(name)
+}
+
+// The span is where the identifier was defined, if it was defined anywhere:
+// prelude names and the results of blocks don't have one.
+pub fn identifier(id: &RichIdentifier, spans: &HashMap<Identifier, Value>) -> Value {
+    json!({
+        "id": id.id,
+        "name": id.name,
+        "span": spans.get(&id.id).cloned().unwrap_or(Value::Null),
+    })
+}
+
+pub fn expression(expr: &Expression, spans: &HashMap<Identifier, Value>) -> Value {
+    use Expression::*;
+    let all = |exprs: &[Expression]| {
+        exprs
+            .iter()
+            .map(|expr| expression(expr, spans))
+            .collect::<Vec<_>>()
+    };
+    match expr {
+        Compound(assignments, result) => {
+            let mut assignments = assignments.iter().collect::<Vec<_>>();
+            assignments.sort_by_key(|(id, _)| id.id);
+            let assignments = assignments
+                .into_iter()
+                .map(|(id, value)| {
+                    json!({
+                        "identifier": identifier(id, spans),
+                        "value": expression(value, spans),
+                    })
+                })
+                .collect::<Vec<_>>();
+            json!({
+                "kind": "compound",
+                "assignments": assignments,
+                "result": expression(result, spans),
+            })
+        }
+        Lambda(params, body, pos) => {
+            let params = params
+                .iter()
+                .map(|(param, pos)| {
+                    json!({"identifier": identifier(param, spans), "pos": part_of_speech(*pos)})
+                })
+                .collect::<Vec<_>>();
+            json!({
+                "kind": "lambda",
+                "params": params,
+                "body": expression(body, spans),
+                "pos": part_of_speech(*pos),
+            })
+        }
+        Conditional(condition, consequent, alternative) => json!({
+            "kind": "conditional",
+            "condition": expression(condition, spans),
+            "consequent": expression(consequent, spans),
+            "alternative": expression(alternative, spans),
+        }),
+        Implicit(b) => json!({"kind": "implicit", "builtin": builtin(*b)}),
+        Identifier(id) => json!({"kind": "identifier", "identifier": identifier(id, spans)}),
+        NumericLiteral(num) => json!({"kind": "number", "value": num}),
+        Parens(inner) => json!({"kind": "parens", "expression": expression(inner, spans)}),
+        Tuple(exprs) => json!({"kind": "tuple", "elements": all(exprs)}),
+        Brackets(exprs) => json!({"kind": "brackets", "elements": all(exprs)}),
+        Element(tuple, index, length) => json!({
+            "kind": "element",
+            "tuple": expression(tuple, spans),
+            "index": index,
+            "length": length,
+        }),
+        UnaryApplication(f, x) => json!({
+            "kind": "unary",
+            "function": expression(f, spans),
+            "argument": expression(x, spans),
+        }),
+        BinaryApplication(f, lhs, rhs) => json!({
+            "kind": "binary",
+            "function": expression(f, spans),
+            "left": expression(lhs, spans),
+            "right": expression(rhs, spans),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn test(input: &str) -> Value {
+        parse_to_json(input).unwrap()
+    }
+
+    #[test]
+    fn test_program() {
+        k9::snapshot!(
+            serde_json::to_string_pretty(&test("x = 1\nneg x")).unwrap(),
+            r#"
+{
+  "expression": {
+    "assignments": [
+      {
+        "identifier": {
+          "id": 26,
+          "name": "x",
+          "span": {
+            "end": 1,
+            "line": 1,
+            "start": 0
+          }
+        },
+        "value": {
+          "kind": "number",
+          "value": "1"
+        }
+      }
+    ],
+    "kind": "compound",
+    "result": {
+      "argument": {
+        "identifier": {
+          "id": 26,
+          "name": "x",
+          "span": {
+            "end": 1,
+            "line": 1,
+            "start": 0
+          }
+        },
+        "kind": "identifier"
+      },
+      "function": {
+        "identifier": {
+          "id": 13,
+          "name": "neg",
+          "span": null
+        },
+        "kind": "identifier"
+      },
+      "kind": "unary"
+    }
+  },
+  "pos": {
+    "part": "noun"
+  },
+  "references": [
+    {
+      "id": 13,
+      "name": "neg",
+      "span": {
+        "end": 9,
+        "line": 2,
+        "start": 6
+      }
+    },
+    {
+      "id": 26,
+      "name": "x",
+      "span": {
+        "end": 11,
+        "line": 2,
+        "start": 10
+      }
+    }
+  ]
+}
+"#
+        );
+    }
+
+    #[test]
+    fn test_implicit_builtins() {
+        let json = test("(neg sign) [1 2]");
+        k9::snapshot!(
+            json["expression"]["result"]["function"]["expression"]["function"].to_string(),
+            r#"{"builtin":"compose","kind":"implicit"}"#
+        );
+        k9::snapshot!(
+            json["expression"]["result"]["argument"].to_string(),
+            r#"{"elements":[{"kind":"number","value":"1"},{"kind":"number","value":"2"}],"kind":"brackets"}"#
+        );
+    }
+
+    #[test]
+    fn test_definitions() {
+        let json = test("twice (f : v1) : v1 = f f\n(twice neg) 1");
+        let definition = &json["expression"]["assignments"][0]["value"];
+        k9::snapshot!(
+            definition["params"].to_string(),
+            r#"[{"identifier":{"id":27,"name":"f","span":{"end":8,"line":1,"start":7}},"pos":{"arity":1,"part":"verb"}}]"#
+        );
+        k9::snapshot!(
+            definition["pos"].to_string(),
+            r#"{"arity":1,"part":"adverb","result_arity":1}"#
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        k9::snapshot!(
+            parse_to_json("x = zed\nx").unwrap_err().to_string(),
+            "x (line 1): zed is not defined"
+        );
+    }
+}
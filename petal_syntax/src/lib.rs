@@ -1,27 +1,71 @@
 mod coefficient_grouper;
+pub mod dependencies;
+pub mod dump;
 pub mod expression;
+pub mod formatter;
 mod helpers;
+pub mod highlight;
+pub mod json;
 mod located_token;
-mod location;
+pub mod location;
 mod op_splitter;
 pub mod pos_parser;
+pub mod prelude;
+pub mod printer;
 mod semicolons;
+pub mod session;
 mod span;
 mod statement;
 pub mod statement_parser;
+mod suggestions;
 mod terms;
 pub mod token;
 pub mod tokenizer;
 mod tokens;
 
 use expression::Expression;
-use pos_parser::{ParseError, PartOfSpeech};
+use pos_parser::{ParseError, PartOfSpeech, Script, Symbols};
 
 pub fn parse(input: &str) -> Result<(Expression, PartOfSpeech), ParseError> {
-    let tokens = tokenizer::tokenize(input);
-    let statements = statement_parser::parse_tokens(tokens).unwrap();
+    pos_parser::just_parse(statements(tokenizer::tokenize(input)?)?)
+}
+
+pub fn parse_script(input: &str) -> Result<Script, ParseError> {
+    pos_parser::parse_script(statements(tokenizer::tokenize(input)?)?)
+}
+
+pub fn parse_with_symbols(
+    input: &str,
+) -> (Result<(Expression, PartOfSpeech), ParseError>, Symbols) {
+    match tokenizer::tokenize(input).and_then(statements) {
+        Ok(statements) => pos_parser::parse_with_symbols(statements),
+        Err(error) => (Err(error), Symbols::default()),
+    }
+}
+
+fn statements(
+    tokens: Vec<located_token::LocatedToken>,
+) -> Result<statement::Block<terms::Term>, ParseError> {
+    let statements = statement_parser::parse_tokens(tokens)?;
     let statements = semicolons::rewrite(statements);
     let statements = op_splitter::rewrite(statements);
-    let statements = coefficient_grouper::rewrite(statements);
-    pos_parser::just_parse(statements)
+    Ok(coefficient_grouper::rewrite(statements))
+}
+
+// The first half of statements, which only looks at the tokens it's given.
+fn soupy_statements(
+    tokens: Vec<located_token::LocatedToken>,
+) -> Result<statement::Block<terms::SoupyTerm>, ParseError> {
+    let statements = statement_parser::parse_tokens(tokens)?;
+    Ok(semicolons::rewrite(statements))
+}
+
+// The second half of statements, for some of the statements of the top-level
+// block, given every name defined at the top level.
+fn split_statements(
+    statements: statement::Block<terms::SoupyTerm>,
+    names: &[String],
+) -> statement::Block<terms::Term> {
+    let statements = op_splitter::rewrite_part(statements, names);
+    coefficient_grouper::rewrite(statements)
 }
@@ -1,10 +1,19 @@
 use crate::span::Span;
+use std::fmt;
 
-#[derive(Debug, Clone)]
-#[allow(dead_code)]
-pub(super) struct Location {
-    offset: usize,
-    line: u32,
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Location {
+    pub offset: usize,
+    pub line: u32,
+}
+
+// Where a part of a document begins: its offset, and the number of lines above
+// it. Locations within the part can be relative to its origin, so that they
+// stay the same when an edit above it moves the whole part.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Origin {
+    pub(super) offset: usize,
+    pub(super) lines: u32,
 }
 
 impl Location {
@@ -14,4 +23,19 @@ impl Location {
             line: span.location_line(),
         }
     }
+
+    // The same place in a part of a document, after that part moves from one
+    // origin to another.
+    pub(super) fn moved(self, from: Origin, to: Origin) -> Location {
+        Location {
+            offset: self.offset - from.offset + to.offset,
+            line: self.line - from.lines + to.lines,
+        }
+    }
+}
+
+impl fmt::Display for Location {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}", self.line)
+    }
 }
@@ -1,45 +1,111 @@
-use std::cmp::Reverse;
+use std::collections::HashMap;
 
+use crate::location::Location;
 use crate::statement::*;
-use crate::terms::{SouplessTerm, SoupyTerm};
+use crate::terms::{Name, SouplessTerm, SoupyTerm};
 
-// So yeah okay. Here's what we're gonna do. we're only going to keep track of
-// multi-character tokens.
-fn split_tokens(mut input: &str, scope: &Scope) -> Vec<SouplessTerm> {
+// Splits soup into the longest operators that are in scope, from left to
+// right. If that doesn't work, the whole soup is unknown, and it's up to the
+// parser to complain about it.
+fn split_tokens(soup: &Name, scope: &Scope) -> Vec<SouplessTerm> {
     let mut result = Vec::new();
+    let mut input = soup.name.as_str();
 
-    // TODO: this is a little dumb. the scope should know *all* defined
-    // operators -- it shouldn't fallback to splitting on single characters. it
-    // would probably be nicer to get an error here.
     while !input.is_empty() {
-        if let Some((op, remaining)) = scope.strip_prefix(input) {
-            if op == "-" {
-                result.push(SouplessTerm::MinusOperator);
-            } else {
-                result.push(SouplessTerm::Operator(op.to_string()));
+        let (op, remaining) = match scope.strip_prefix(input) {
+            Some(split) => split,
+            None => {
+                let splits = possible_splits(&soup.name, scope);
+                return vec![SouplessTerm::UnknownOperator(soup.clone(), splits)];
             }
-            input = remaining;
+        };
+        // every operator is on the same line as the soup it came from
+        let location = Location {
+            offset: soup.location.offset + (soup.name.len() - input.len()),
+            line: soup.location.line,
+        };
+        if op == "-" {
+            result.push(SouplessTerm::MinusOperator(location));
         } else {
-            let op = &input[0..1];
-            if op == "-" {
-                result.push(SouplessTerm::MinusOperator);
-            } else {
-                result.push(SouplessTerm::Operator(op.to_string()));
-            }
-            input = &input[1..];
+            result.push(SouplessTerm::Operator(Name {
+                name: op.to_string(),
+                location,
+            }));
         }
+        input = remaining;
     }
     result
 }
 
+const MAX_SPLITS: usize = 3;
+
+// Other ways to split the soup into operators that are in scope, which taking
+// the longest operator first can miss: with |, |&, and && in scope, |&& can
+// be | &&, even though |& & doesn't work.
+fn possible_splits(soup: &str, scope: &Scope) -> Vec<String> {
+    // splittable[i] is whether soup[i..] can be split into operators, which
+    // keeps the search below from trying every dead end
+    let mut splittable = vec![false; soup.len() + 1];
+    splittable[soup.len()] = true;
+    for (i, _) in soup.char_indices().rev() {
+        splittable[i] = scope
+            .prefixes(&soup[i..])
+            .iter()
+            .any(|len| splittable[i + len]);
+    }
+    let mut splits = vec![];
+    collect_splits(soup, 0, scope, &splittable, &mut vec![], &mut splits);
+    splits
+}
+
+fn collect_splits<'s>(
+    soup: &'s str,
+    start: usize,
+    scope: &Scope,
+    splittable: &[bool],
+    ops: &mut Vec<&'s str>,
+    splits: &mut Vec<String>,
+) {
+    if splits.len() == MAX_SPLITS {
+        return;
+    }
+    if start == soup.len() {
+        splits.push(ops.join(" "));
+        return;
+    }
+    for len in scope.prefixes(&soup[start..]) {
+        if splittable[start + len] {
+            ops.push(&soup[start..start + len]);
+            collect_splits(soup, start + len, scope, splittable, ops, splits);
+            ops.pop();
+        }
+    }
+}
+
 #[test]
 fn test_split_tokens() {
-    fn test(input: &str, ops: &[&str]) -> Vec<SouplessTerm> {
-        let mut scope = Scope::new(None);
+    fn test(input: &str, ops: &[&str]) -> String {
+        let prelude = get_initial_scope();
+        let mut scope = Scope::new(Some(&prelude));
         for op in ops {
             scope.learn(op);
         }
-        split_tokens(input, &scope)
+        let soup = Name {
+            name: input.to_string(),
+            location: Location { offset: 0, line: 1 },
+        };
+        let terms = split_tokens(&soup, &scope)
+            .iter()
+            .map(|term| match term {
+                SouplessTerm::Operator(op) => format!("Operator({:?})", op.name),
+                SouplessTerm::MinusOperator(_) => "MinusOperator".to_string(),
+                SouplessTerm::UnknownOperator(soup, splits) => {
+                    format!("UnknownOperator({:?}, {:?})", soup.name, splits)
+                }
+                term => panic!("unexpected term {:?}", term),
+            })
+            .collect::<Vec<_>>();
+        format!("[{}]", terms.join(", "))
     }
 
     k9::snapshot!(
@@ -52,13 +118,117 @@ fn test_split_tokens() {
         test("+-+", &["-+", "+-"]),
         r#"[Operator("+-"), Operator("+")]"#
     );
+    k9::snapshot!(
+        test("<<<<<", &["<<", "<<<<"]),
+        r#"[Operator("<<<<"), Operator("<")]"#
+    );
+    // prefixes of operators aren't operators
+    k9::snapshot!(test("<|<|>", &["<|>"]), r#"[UnknownOperator("<|<|>", [])]"#);
+    k9::snapshot!(
+        test("<|><|>", &["<|>"]),
+        r#"[Operator("<|>"), Operator("<|>")]"#
+    );
+}
+
+#[test]
+fn test_unknown_operators() {
+    fn test(input: &str, ops: &[&str]) -> String {
+        let mut scope = Scope::new(None);
+        for op in ops {
+            scope.learn(op);
+        }
+        let soup = Name {
+            name: input.to_string(),
+            location: Location { offset: 0, line: 1 },
+        };
+        match split_tokens(&soup, &scope).as_slice() {
+            [SouplessTerm::UnknownOperator(_, splits)] => format!("{:?}", splits),
+            terms => panic!("split into {:?}", terms),
+        }
+    }
+
+    k9::snapshot!(test("|&&", &["|", "|&", "&&"]), r#"["| &&"]"#);
+    k9::snapshot!(test("|&", &["|"]), "[]");
+    // taking the longest operator first leaves <? behind
+    k9::snapshot!(
+        test("<<<<?", &["<", "<<", "<<<", "<<?"]),
+        r#"["<< <<?", "< < <<?"]"#
+    );
+}
+
+#[test]
+fn test_nested_scopes() {
+    fn split(input: &str, outer: &Scope) -> String {
+        let soup = Name {
+            name: input.to_string(),
+            location: Location { offset: 0, line: 1 },
+        };
+        split_tokens(&soup, outer)
+            .iter()
+            .map(|term| match term {
+                SouplessTerm::Operator(op) => op.name.clone(),
+                SouplessTerm::MinusOperator(_) => "-".to_string(),
+                term => panic!("unexpected term {:?}", term),
+            })
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+
+    let prelude = get_initial_scope();
+    let mut outer = Scope::new(Some(&prelude));
+    outer.learn("<<<");
+    outer.learn("|>");
+    let mut inner = Scope::new(Some(&outer));
+    inner.learn("<<");
+    inner.learn("|>>");
+
+    k9::snapshot!(split("<<<<<|>>", &inner), "<<< << |>>");
+    k9::snapshot!(split("<<<<<|>>", &outer), "<<< < < |> >");
+    k9::snapshot!(split("<<|><<<", &inner), "<< |> <<<");
+}
+
+#[test]
+fn test_forward_references() {
+    fn test(input: &str) -> String {
+        crate::dump::dump(input, crate::dump::Stage::Soupless)
+            .unwrap()
+            .trim_end()
+            .to_string()
+    }
+
+    k9::snapshot!(
+        test("x = 1 <+> 2\n<+> = +\nx"),
+        "
+x = 1 ␠ <+> ␠ 2
+<+> = +
+x
+"
+    );
+    k9::snapshot!(
+        test("<+> : v2\nx = 1 <+> 2"),
+        "
+<+> : v2
+x = 1 ␠ <+> ␠ 2
+"
+    );
+    // operators from an inner block don't leak out, even after it
+    k9::snapshot!(
+        test("x = 1 <+> 2\ny =\n  <+> = +\n  1 <+> 2\nx"),
+        "
+x = 1 ␠ < + > ␠ 2
+y =
+  <+> = +
+  1 ␠ <+> ␠ 2
+x
+"
+    );
 }
 
 fn get_initial_scope<'a>() -> Scope<'a> {
     let mut scope = Scope::new(None);
-    // TODO: fill out all the built-in operators here
-    scope.learn(">=");
-    scope.learn("<=");
+    for (name, _) in crate::prelude::PRELUDE {
+        scope.learn(name);
+    }
     scope
 }
 
@@ -67,86 +237,151 @@ fn split(terms: Vec<SoupyTerm>, scope: &Scope) -> Vec<SouplessTerm> {
         .into_iter()
         .flat_map(|term: SoupyTerm| match term {
             SoupyTerm::Identifier(s) => vec![SouplessTerm::Identifier(s)],
-            SoupyTerm::PunctuationSoup(s) => split_tokens(&s, scope),
+            SoupyTerm::PunctuationSoup(soup) => split_tokens(&soup, scope),
             SoupyTerm::NumericLiteral(s) => vec![SouplessTerm::NumericLiteral(s)],
-            SoupyTerm::Parens(terms) => vec![SouplessTerm::Parens(split(terms, scope))],
-            SoupyTerm::Brackets(terms) => vec![SouplessTerm::Brackets(split(terms, scope))],
+            SoupyTerm::Parens(terms, delimiters) => {
+                vec![SouplessTerm::Parens(split(terms, scope), delimiters)]
+            }
+            SoupyTerm::Brackets(terms, delimiters) => {
+                vec![SouplessTerm::Brackets(split(terms, scope), delimiters)]
+            }
             SoupyTerm::Space => vec![SouplessTerm::Space],
         })
         .collect()
 }
 
+// Operators stored a character at a time, so that finding the longest one at
+// the start of a soup only looks at each character once.
+#[derive(Default)]
+struct Trie {
+    children: HashMap<char, Trie>,
+    terminal: bool,
+}
+
+impl Trie {
+    fn insert(&mut self, op: &str) {
+        let mut node = self;
+        for c in op.chars() {
+            node = node.children.entry(c).or_default();
+        }
+        node.terminal = true;
+    }
+
+    // the lengths in bytes of every operator that the input starts with,
+    // shortest first
+    fn prefixes(&self, input: &str) -> Vec<usize> {
+        let mut node = self;
+        let mut lengths = vec![];
+        for (i, c) in input.char_indices() {
+            node = match node.children.get(&c) {
+                Some(child) => child,
+                None => break,
+            };
+            if node.terminal {
+                lengths.push(i + c.len_utf8());
+            }
+        }
+        lengths
+    }
+
+    fn longest_prefix(&self, input: &str) -> Option<usize> {
+        self.prefixes(input).pop()
+    }
+}
+
 struct Scope<'a> {
-    operators: Vec<String>,
+    operators: Trie,
     parent: Option<&'a Scope<'a>>,
 }
 
-// TODO: convert this to a trie. horribly inefficient searching and adding
 impl<'a> Scope<'a> {
     fn new(parent: Option<&'a Scope<'a>>) -> Self {
         Scope {
-            operators: vec![],
+            operators: Trie::default(),
             parent,
         }
     }
 
     fn learn(&mut self, op: &str) {
-        if op.len() > 1 {
-            self.operators.push(op.to_string())
-        }
-        // TODO: yes i am aware how terrible this is
-        self.operators.sort_by_key(|s| Reverse(s.len()));
+        self.operators.insert(op)
     }
 
-    fn strip_my_prefix<'s>(&self, input: &'s str) -> Option<(&str, &'s str)> {
-        for known_token in &self.operators {
-            if let Some(remaining) = input.strip_prefix(known_token) {
-                return Some((known_token, remaining));
-            }
+    // The longest operator defined in this scope or any scope above it. An
+    // operator in an inner scope doesn't hide a longer one from outside.
+    fn strip_prefix<'s>(&self, input: &'s str) -> Option<(&'s str, &'s str)> {
+        let mut longest = None;
+        let mut scope = Some(self);
+        while let Some(current) = scope {
+            longest = longest.max(current.operators.longest_prefix(input));
+            scope = current.parent;
         }
-        None
+        longest.map(|len| input.split_at(len))
     }
 
-    fn strip_prefix<'s>(&self, input: &'s str) -> Option<(&str, &'s str)> {
-        let mine = self.strip_my_prefix(input);
-        let parent = match self.parent {
-            None => None,
-            Some(parent) => parent.strip_prefix(input),
-        };
-        match (mine, parent) {
-            (Some((op1, _)), Some((op2, _))) => {
-                if op1.len() > op2.len() {
-                    mine
-                } else {
-                    parent
-                }
-            }
-            (Some(_), None) => mine,
-            (None, Some(_)) => parent,
-            (None, None) => None,
+    // the lengths of every operator in scope that the input starts with,
+    // longest first
+    fn prefixes(&self, input: &str) -> Vec<usize> {
+        let mut lengths = vec![];
+        let mut scope = Some(self);
+        while let Some(current) = scope {
+            lengths.extend(current.operators.prefixes(input));
+            scope = current.parent;
         }
+        lengths.sort_by(|a, b| b.cmp(a));
+        lengths.dedup();
+        lengths
     }
 }
 
+// Names can be used before they're defined, as long as they're defined (or
+// declared) somewhere in the same block, so we learn every operator in the
+// block before we split anything.
 fn rewrite_block(block: Block<SoupyTerm>, parent_scope: &Scope) -> Block<SouplessTerm> {
-    use Statement::*;
     let mut scope = Scope::new(Some(parent_scope));
+    for name in defined_names(&block) {
+        scope.learn(name);
+    }
     block
         .into_iter()
-        .map(|statement: Statement<SoupyTerm>| match statement {
-            SimpleAssignment(id, terms) => {
-                scope.learn(&id);
-                SimpleAssignment(id, split(terms, &scope))
-            }
-            CompoundAssignment(id, block) => {
-                scope.learn(&id);
-                CompoundAssignment(id, rewrite_block(block, &scope))
-            }
-            Expression(terms) => Expression(split(terms, &scope)),
-        })
+        .map(|statement| rewrite_statement(statement, &scope))
         .collect()
 }
 
+// Every name that the statements of a block assign or declare.
+pub(super) fn defined_names(block: &Block<SoupyTerm>) -> Vec<&str> {
+    use Statement::*;
+    let mut names = vec![];
+    for statement in block {
+        match statement {
+            SimpleAssignment(id, _, _) | CompoundAssignment(id, _, _) | Declaration(id, _) => {
+                names.push(id.name.as_str())
+            }
+            Destructuring(ids, _) => names.extend(ids.iter().map(|id| id.name.as_str())),
+            Expression(_) | Conditional(_, _, _, _) => (),
+        }
+    }
+    names
+}
+
+fn rewrite_statement(statement: Statement<SoupyTerm>, scope: &Scope) -> Statement<SouplessTerm> {
+    use Statement::*;
+    match statement {
+        SimpleAssignment(id, sig, terms) => SimpleAssignment(id, sig, split(terms, scope)),
+        CompoundAssignment(id, sig, block) => {
+            CompoundAssignment(id, sig, rewrite_block(block, scope))
+        }
+        Expression(terms) => Expression(split(terms, scope)),
+        Declaration(id, sig) => Declaration(id, sig),
+        Destructuring(names, terms) => Destructuring(names, split(terms, scope)),
+        Conditional(terms, consequent, otherwise, alternative) => Conditional(
+            split(terms, scope),
+            rewrite_block(consequent, scope),
+            otherwise,
+            rewrite_block(alternative, scope),
+        ),
+    }
+}
+
 #[cfg(test)]
 pub(super) fn split_expression(terms: Vec<SoupyTerm>) -> Vec<SouplessTerm> {
     let prelude = get_initial_scope();
@@ -157,3 +392,17 @@ pub(super) fn rewrite(block: Block<SoupyTerm>) -> Block<SouplessTerm> {
     let prelude = get_initial_scope();
     rewrite_block(block, &prelude)
 }
+
+// Like rewrite, but for only some of the statements of a top-level block. names
+// is every name defined anywhere in the block.
+pub(super) fn rewrite_part(statements: Block<SoupyTerm>, names: &[String]) -> Block<SouplessTerm> {
+    let prelude = get_initial_scope();
+    let mut scope = Scope::new(Some(&prelude));
+    for name in names {
+        scope.learn(name);
+    }
+    statements
+        .into_iter()
+        .map(|statement| rewrite_statement(statement, &scope))
+        .collect()
+}
@@ -1,6 +1,15 @@
 use crate::expression::{Builtin, Expression, Identifier, RichIdentifier};
-use crate::terms::Term;
-use std::{cell::RefCell, collections::HashMap, fmt, rc::Rc};
+use crate::location::{Location, Origin};
+use crate::statement::Signature;
+use crate::suggestions::suggestions;
+use crate::terms::{Name, Term};
+use std::{
+    cell::{Cell, RefCell},
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+    ops::Range,
+    rc::Rc,
+};
 
 type Statement = crate::statement::Statement<Term>;
 
@@ -21,18 +30,277 @@ pub enum Arity {
 pub enum PartOfSpeech {
     Noun,
     Verb(Arity),
-    Adverb(Arity, Arity), // input arity, output arity
+    // input arity, output arity, and what each operand has to be. Unary
+    // adverbs only look at the first operand.
+    Adverb(Arity, Arity, [Operand; 2]),
 }
 use PartOfSpeech::*;
 
-#[derive(Debug)]
+// What an adverb can be applied to. Definitions declare the part of speech of
+// each of their parameters, but builtin adverbs check their operands when
+// they're applied, so they take anything.
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Operand {
+    Any,
+    Noun,
+    Verb(Arity),
+}
+
+pub const ANY_OPERANDS: [Operand; 2] = [Operand::Any, Operand::Any];
+
+// An assignment, and where it appears in the source. Assignments that come
+// from bare expressions have no name of their own, so they're called _ and
+// have no location.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Binding {
+    pub id: Identifier,
+    pub name: String,
+    pub location: Option<Location>,
+}
+
+#[derive(Debug, Clone)]
 pub enum ParseError {
     DidNotFullyReduce(Vec<(Expression, PartOfSpeech)>),
     ArrayLiteralNotNoun,
-    BadReference(Identifier),
-    SubAssignmentFailed,
-    CyclicAssignments,
+    // the binding that failed on its own, and why, when a failure propagates
+    // to everything that depends on it
+    BadReference(Binding, Box<ParseError>),
+    // every assignment in a block that failed, in order
+    SubAssignmentsFailed(Vec<(Binding, ParseError)>),
+    // every binding in the cycle, each of which depends on the next, and the
+    // last of which depends on the first
+    CyclicAssignments(Vec<Binding>),
+    // a name that isn't defined anywhere, where it was used, and names that are
+    // in scope there that it might be a typo of
+    UnboundName(String, Location, Vec<String>),
+    // punctuation that isn't made of operators that are in scope, where it
+    // was, and ways that it could be split up with spaces
+    UnknownOperator(String, Location, Vec<String>),
     BlockWithoutResult,
+    InvalidSignature,
+    PartOfSpeechMismatch(PartOfSpeech, PartOfSpeech), // declared, actual
+    // the adverb, which of its operands, what that operand was declared as, and
+    // what it was given
+    OperandMismatch(String, usize, Operand, PartOfSpeech),
+    DeclarationWithoutDefinition,
+    // declared again before the declaration was used
+    DuplicateDeclaration,
+    // the first token that couldn't be parsed into a statement
+    InvalidSyntax(Location),
+    // the first line that was indented differently than the ones before it
+    MixedIndentation(Location),
+    // a name that a destructuring assignment binds more than once, and where
+    // it's bound again
+    BoundTwice(String, Location),
+}
+
+// A name in the source, what it was resolved to, and its part of speech, if
+// it has one. Definitions that fail to parse have no part of speech unless
+// it was declared, and neither do references to them.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Symbol {
+    pub id: Identifier,
+    pub name: String,
+    pub location: Location,
+    pub pos: Option<PartOfSpeech>,
+}
+
+// Every named definition and every reference to a name in a program, and
+// every implicit builtin, for editors. References to the prelude have no
+// corresponding definition.
+#[derive(Debug, Default, Clone)]
+pub struct Symbols {
+    pub definitions: Vec<Symbol>,
+    pub references: Vec<Symbol>,
+    pub implicit: Vec<Implicit>,
+}
+
+impl Symbols {
+    fn extend(&mut self, other: Symbols) {
+        self.definitions.extend(other.definitions);
+        self.references.extend(other.references);
+        self.implicit.extend(other.implicit);
+    }
+
+    fn sorted(mut self) -> Symbols {
+        self.definitions
+            .sort_by_key(|symbol| symbol.location.offset);
+        self.references.sort_by_key(|symbol| symbol.location.offset);
+        self.implicit.sort_by_key(|implicit| implicit.extent.start);
+        self
+    }
+
+    fn moved(mut self, from: Origin, to: Origin) -> Symbols {
+        for symbol in self.definitions.iter_mut().chain(&mut self.references) {
+            symbol.location = symbol.location.moved(from, to);
+        }
+        for implicit in &mut self.implicit {
+            let start = implicit.extent.start - from.offset + to.offset;
+            implicit.extent = start..start + implicit.extent.len();
+        }
+        self
+    }
+}
+
+// A single problem with a program, along with the assignments that it
+// happened inside of, outermost first.
+#[derive(Debug)]
+pub struct Failure<'a> {
+    pub bindings: Vec<&'a Binding>,
+    pub error: &'a ParseError,
+}
+
+impl Failure<'_> {
+    // Failures that only happened because something else failed are
+    // BadReferences. Everything else is a root cause.
+    pub fn is_root_cause(&self) -> bool {
+        !matches!(self.error, ParseError::BadReference(_, _))
+    }
+}
+
+impl ParseError {
+    // Flattens nested assignment failures into a list of every failed
+    // assignment in the program, in source order.
+    pub fn failures(&self) -> Vec<Failure<'_>> {
+        let mut failures = vec![];
+        self.collect_failures(&mut vec![], &mut failures);
+        failures
+    }
+
+    // Just the failures that aren't a consequence of other failures.
+    pub fn root_causes(&self) -> Vec<Failure<'_>> {
+        self.failures()
+            .into_iter()
+            .filter(Failure::is_root_cause)
+            .collect()
+    }
+
+    fn collect_failures<'a>(
+        &'a self,
+        path: &mut Vec<&'a Binding>,
+        failures: &mut Vec<Failure<'a>>,
+    ) {
+        match self {
+            ParseError::SubAssignmentsFailed(assignments) => {
+                for (binding, error) in assignments {
+                    path.push(binding);
+                    error.collect_failures(path, failures);
+                    path.pop();
+                }
+            }
+            error => failures.push(Failure {
+                bindings: path.clone(),
+                error,
+            }),
+        }
+    }
+}
+
+impl fmt::Display for Failure<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // a cycle already names the binding that it failed
+        let bindings = match self.error {
+            ParseError::CyclicAssignments(_) => &self.bindings[..self.bindings.len() - 1],
+            _ => &self.bindings,
+        };
+        for binding in bindings {
+            write!(f, "{}: ", binding)?;
+        }
+        write!(f, "{}", self.error)
+    }
+}
+
+impl fmt::Display for Binding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.location {
+            Some(location) => write!(f, "{} ({})", self.name, location),
+            None => write!(f, "{}", self.name),
+        }
+    }
+}
+
+fn write_suggestions(f: &mut fmt::Formatter<'_>, suggestions: &[String]) -> fmt::Result {
+    if suggestions.is_empty() {
+        return Ok(());
+    }
+    write!(f, "; did you mean ")?;
+    let last = suggestions.len() - 1;
+    for (i, suggestion) in suggestions.iter().enumerate() {
+        let separator = match i {
+            0 => "",
+            _ if i < last => ", ",
+            1 => " or ",
+            _ => ", or ",
+        };
+        write!(f, "{}{}", separator, suggestion)?;
+    }
+    write!(f, "?")
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use ParseError::*;
+        match self {
+            DidNotFullyReduce(parts) => {
+                write!(f, "expression did not fully reduce:")?;
+                for (expr, pos) in parts {
+                    write!(f, " {} ({})", expr, pos)?;
+                }
+                Ok(())
+            }
+            ArrayLiteralNotNoun => write!(f, "array literals can only contain nouns"),
+            BadReference(binding, cause) => {
+                write!(f, "depends on {}, which failed: {}", binding, cause)
+            }
+            SubAssignmentsFailed(_) => {
+                for (i, failure) in self.root_causes().iter().enumerate() {
+                    if i != 0 {
+                        writeln!(f)?;
+                    }
+                    write!(f, "{}", failure)?;
+                }
+                Ok(())
+            }
+            CyclicAssignments(bindings) => {
+                write!(f, "cyclic definitions: ")?;
+                for binding in bindings {
+                    write!(f, "{} -> ", binding)?;
+                }
+                write!(f, "{}", bindings[0].name)
+            }
+            UnboundName(name, _, suggestions) => {
+                write!(f, "{} is not defined", name)?;
+                write_suggestions(f, suggestions)
+            }
+            UnknownOperator(soup, _, splits) => {
+                write!(f, "unknown operator {}", soup)?;
+                write_suggestions(f, splits)
+            }
+            BlockWithoutResult => write!(f, "block has no result"),
+            InvalidSignature => write!(f, "invalid signature"),
+            PartOfSpeechMismatch(declared, actual) => {
+                write!(f, "declared as {} but defined as {}", declared, actual)
+            }
+            OperandMismatch(adverb, index, declared, actual) => {
+                let ordinal = match index {
+                    0 => "first",
+                    _ => "second",
+                };
+                write!(
+                    f,
+                    "the {} operand of {} is declared as {} but given {}",
+                    ordinal, adverb, declared, actual
+                )
+            }
+            DeclarationWithoutDefinition => write!(f, "declared but never defined"),
+            DuplicateDeclaration => write!(f, "declared again before being defined"),
+            InvalidSyntax(location) => write!(f, "invalid syntax on {}", location),
+            MixedIndentation(location) => {
+                write!(f, "indentation mixes tabs and spaces on {}", location)
+            }
+            BoundTwice(name, location) => write!(f, "{} is bound twice on {}", name, location),
+        }
+    }
 }
 
 impl fmt::Display for PartOfSpeech {
@@ -41,15 +309,104 @@ impl fmt::Display for PartOfSpeech {
             Noun => write!(f, "n"),
             Verb(Arity::Unary) => write!(f, "v1"),
             Verb(Arity::Binary) => write!(f, "v2"),
-            Adverb(Arity::Unary, _) => write!(f, "a1"),
-            Adverb(Arity::Binary, _) => write!(f, "a2"),
+            Adverb(Arity::Unary, _, _) => write!(f, "a1"),
+            Adverb(Arity::Binary, _, _) => write!(f, "a2"),
+        }
+    }
+}
+
+impl fmt::Display for Operand {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Operand::Any => write!(f, "anything"),
+            Operand::Noun => write!(f, "{}", Noun),
+            Operand::Verb(arity) => write!(f, "{}", Verb(*arity)),
+        }
+    }
+}
+
+impl Operand {
+    fn accepts(self, pos: PartOfSpeech) -> bool {
+        match self {
+            Operand::Any => true,
+            Operand::Noun => pos == Noun,
+            Operand::Verb(arity) => pos == Verb(arity),
+        }
+    }
+}
+
+impl Arity {
+    fn of_count(count: usize) -> Option<Self> {
+        match count {
+            1 => Some(Arity::Unary),
+            2 => Some(Arity::Binary),
+            _ => None,
+        }
+    }
+}
+
+impl Signature {
+    // Definitions whose operands and result are all nouns are verbs. Otherwise
+    // the definition produces a verb from its operands, which makes it an
+    // adverb. A unary adverb can only ever be applied to a verb, so its operand
+    // can't be a noun.
+    fn part_of_speech(&self) -> Result<PartOfSpeech, ParseError> {
+        if self.params.is_empty() {
+            return Ok(self.result);
+        }
+        let arity = Arity::of_count(self.params.len()).ok_or(ParseError::InvalidSignature)?;
+        let all_nouns = self.params.iter().all(|(_, pos)| *pos == Noun);
+        let mut operands = ANY_OPERANDS;
+        for (operand, (_, pos)) in operands.iter_mut().zip(&self.params) {
+            *operand = match pos {
+                Noun => Operand::Noun,
+                Verb(arity) => Operand::Verb(*arity),
+                Adverb(_, _, _) => return Err(ParseError::InvalidSignature),
+            };
+        }
+        match self.result {
+            Noun if all_nouns => Ok(Verb(arity)),
+            Noun => Err(ParseError::InvalidSignature),
+            Verb(_) if arity == Arity::Unary && all_nouns => Err(ParseError::InvalidSignature),
+            Verb(result_arity) => Ok(Adverb(arity, result_arity, operands)),
+            Adverb(_, _, _) => Err(ParseError::InvalidSignature),
+        }
+    }
+}
+
+// The bytes of source that an expression on the stack was parsed from. This is
+// only used to find implicit builtins, so it's fine that it doesn't include
+// parentheses. Empty parens don't have an extent at all.
+type Extent = Option<Range<usize>>;
+
+fn extent_of(name: &Name) -> Extent {
+    Some(name.location.offset..name.location.offset + name.name.len())
+}
+
+fn cover(first: &Extent, second: &Extent) -> Extent {
+    match (first, second) {
+        (Some(first), Some(second)) => {
+            Some(first.start.min(second.start)..first.end.max(second.end))
         }
+        (Some(extent), None) | (None, Some(extent)) => Some(extent.clone()),
+        (None, None) => None,
     }
 }
 
+// A place where juxtaposition means a builtin that isn't written anywhere.
+// Coefficients are the token of the coefficient; everything else is the source
+// between the two things that were juxtaposed, which might be empty.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Implicit {
+    pub extent: Range<usize>,
+    pub builtin: Builtin,
+}
+
+type StackEntry = Option<(Expression, PartOfSpeech, Extent)>;
+
 #[derive(Debug)]
 struct ParseFrame {
-    stack: Vec<Option<(Expression, PartOfSpeech)>>,
+    stack: Vec<StackEntry>,
     input: Vec<Term>,
     end_reached: bool,
     finish: fn(Expression, PartOfSpeech) -> Result<Expression, ParseError>,
@@ -81,6 +438,7 @@ impl ParseFrame {
 // would only be implementable on Rc<RefCell<BlockParsnip>>, and that's all
 // gross. But maybe worth it? The inverted control flow that PollingName
 // begets is really pretty complicated.
+#[derive(Clone)]
 enum ParseResult {
     Complete(Expression, PartOfSpeech),
     PollingName(String),
@@ -109,19 +467,64 @@ fn wrap_brackets(expr: Expression, pos: PartOfSpeech) -> Result<Expression, Pars
     }
 }
 
-fn pop_expr(stack: &mut Vec<Option<(Expression, PartOfSpeech)>>) -> Expression {
-    let (expr, _) = stack.pop().unwrap().unwrap();
-    expr
+// tuples are built up from the right, so while they're on the stack their
+// elements are stored backwards. we flip them around once, when they're done
+fn finish_tuple(expr: Expression) -> Expression {
+    match expr {
+        Expression::Tuple(mut exprs) => {
+            exprs.reverse();
+            Expression::Tuple(exprs)
+        }
+        expr => expr,
+    }
+}
+
+fn pop_entry(stack: &mut Vec<StackEntry>) -> (Expression, PartOfSpeech, Extent) {
+    let (expr, pos, extent) = stack.pop().unwrap().unwrap();
+    (finish_tuple(expr), pos, extent)
 }
 
-fn pop_adverb(stack: &mut Vec<Option<(Expression, PartOfSpeech)>>) -> (Expression, Arity) {
-    let (expr, pos) = stack.pop().unwrap().unwrap();
+fn pop_expr(stack: &mut Vec<StackEntry>) -> (Expression, Extent) {
+    let (expr, _, extent) = pop_entry(stack);
+    (expr, extent)
+}
+
+fn pop_adverb(stack: &mut Vec<StackEntry>) -> (Expression, Arity, [Operand; 2], Extent) {
+    let (expr, pos, extent) = stack.pop().unwrap().unwrap();
     match pos {
-        Adverb(_, result_arity) => (expr, result_arity),
+        Adverb(_, result_arity, operands) => (expr, result_arity, operands, extent),
         _ => panic!("not an adverb!"),
     }
 }
 
+// the parts of speech of the things an adverb is applied to, in order
+fn check_operands(
+    adverb: &Expression,
+    operands: [Operand; 2],
+    args: &[PartOfSpeech],
+) -> Result<(), ParseError> {
+    for (index, (operand, arg)) in operands.iter().zip(args).enumerate() {
+        if !operand.accepts(*arg) {
+            return Err(ParseError::OperandMismatch(
+                adverb.to_string(),
+                index,
+                *operand,
+                *arg,
+            ));
+        }
+    }
+    Ok(())
+}
+
+// the source between two adjacent expressions, the first of which is to the
+// left of the second
+fn between(first: &Extent, second: &Extent) -> Option<Range<usize>> {
+    match (first, second) {
+        (Some(first), Some(second)) => Some(first.end..second.start),
+        _ => None,
+    }
+}
+
 macro_rules! lookahead {
     ($stack:ident, $block:block) => {{
         let stash = $stack.pop().unwrap();
@@ -131,23 +534,37 @@ macro_rules! lookahead {
 }
 
 macro_rules! bin_impl_lr {
-    ($stack:ident, $inner:path, $pos:expr) => {
-        let lhs = pop_expr($stack);
-        let rhs = pop_expr($stack);
+    ($stack:ident, $implicit:ident, $inner:path, $pos:expr) => {
+        let (lhs, lhs_extent) = pop_expr($stack);
+        let (rhs, rhs_extent) = pop_expr($stack);
+        if let Some(extent) = between(&lhs_extent, &rhs_extent) {
+            $implicit.push(Implicit {
+                extent,
+                builtin: $inner,
+            });
+        }
         $stack.push(Some((
             Expression::binary(Expression::Implicit($inner), lhs, rhs),
             $pos,
+            cover(&lhs_extent, &rhs_extent),
         )));
     };
 }
 
 macro_rules! bin_impl_rl {
-    ($stack:ident, $inner:expr, $pos:expr) => {
-        let rhs = pop_expr($stack);
-        let lhs = pop_expr($stack);
+    ($stack:ident, $implicit:ident, $inner:expr, $pos:expr) => {
+        let (rhs, rhs_extent) = pop_expr($stack);
+        let (lhs, lhs_extent) = pop_expr($stack);
+        if let Some(extent) = between(&rhs_extent, &lhs_extent) {
+            $implicit.push(Implicit {
+                extent,
+                builtin: $inner,
+            });
+        }
         $stack.push(Some((
             Expression::binary(Expression::Implicit($inner), lhs, rhs),
             $pos,
+            cover(&lhs_extent, &rhs_extent),
         )));
     };
 }
@@ -174,27 +591,27 @@ macro_rules! pos {
     };
 
     (n) => {
-        Some((_, Noun))
+        Some((_, Noun, _))
     };
 
     (v) => {
-        Some((_, Verb(_)))
+        Some((_, Verb(_), _))
     };
 
     (v1) => {
-        Some((_, Verb(Unary)))
+        Some((_, Verb(Unary), _))
     };
 
     (v2) => {
-        Some((_, Verb(Binary)))
+        Some((_, Verb(Binary), _))
     };
 
     (a1) => {
-        Some((_, Adverb(Unary, _)))
+        Some((_, Adverb(Unary, _, _), _))
     };
 
     (a2) => {
-        Some((_, Adverb(Binary, _)))
+        Some((_, Adverb(Binary, _, _), _))
     };
 
     (_) => {
@@ -216,43 +633,63 @@ macro_rules! stack {
     };
 }
 
-fn reduce_stack(stack: &mut Vec<Option<(Expression, PartOfSpeech)>>) {
+// Records every implicit builtin that it inserts in implicit.
+fn reduce_stack(
+    stack: &mut Vec<StackEntry>,
+    implicit: &mut Vec<Implicit>,
+) -> Result<(), ParseError> {
     use Arity::*;
 
     loop {
         match &stack[stack.len() - 4..] {
             stack![a1, v] => {
-                let (adverb, result_arity) = pop_adverb(stack);
-                let verb = pop_expr(stack);
-                stack.push(Some((Expression::unary(adverb, verb), Verb(result_arity))));
+                let (adverb, result_arity, operands, adverb_extent) = pop_adverb(stack);
+                let (verb, pos, verb_extent) = pop_entry(stack);
+                check_operands(&adverb, operands, &[pos])?;
+                stack.push(Some((
+                    Expression::unary(adverb, verb),
+                    Verb(result_arity),
+                    cover(&adverb_extent, &verb_extent),
+                )));
             }
 
             stack![svn, v1, n] => lookahead!(stack, {
-                let verb = pop_expr(stack);
-                let noun = pop_expr(stack);
-                stack.push(Some((Expression::unary(verb, noun), Noun)));
+                let (verb, verb_extent) = pop_expr(stack);
+                let (noun, noun_extent) = pop_expr(stack);
+                stack.push(Some((
+                    Expression::unary(verb, noun),
+                    Noun,
+                    cover(&verb_extent, &noun_extent),
+                )));
             }),
 
             stack![_, vn, a2, vn] => lookahead!(stack, {
-                let lhs = pop_expr(stack);
-                let (conjunction, result_arity) = pop_adverb(stack);
-                let rhs = pop_expr(stack);
+                let (lhs, lhs_pos, lhs_extent) = pop_entry(stack);
+                let (conjunction, result_arity, operands, _) = pop_adverb(stack);
+                let (rhs, rhs_pos, rhs_extent) = pop_entry(stack);
+                check_operands(&conjunction, operands, &[lhs_pos, rhs_pos])?;
                 stack.push(Some((
                     Expression::binary(conjunction, lhs, rhs),
                     Verb(result_arity),
+                    cover(&lhs_extent, &rhs_extent),
                 )));
             }),
 
             stack![sv, n, v2, n] => lookahead!(stack, {
-                let lhs = pop_expr(stack);
-                let verb = pop_expr(stack);
-                let rhs = pop_expr(stack);
-                stack.push(Some((Expression::binary(verb, lhs, rhs), Noun)));
+                let (lhs, lhs_extent) = pop_expr(stack);
+                let (verb, _) = pop_expr(stack);
+                let (rhs, rhs_extent) = pop_expr(stack);
+                stack.push(Some((
+                    Expression::binary(verb, lhs, rhs),
+                    Noun,
+                    cover(&lhs_extent, &rhs_extent),
+                )));
             }),
 
             stack![svn, n, n] => lookahead!(stack, {
-                let first = pop_expr(stack);
-                let second = pop_expr(stack);
+                let (first, first_extent) = pop_expr(stack);
+                // don't use pop_expr here, or we'd flip a tuple in progress
+                let (second, _, second_extent) = stack.pop().unwrap().unwrap();
 
                 let result = match second {
                     Expression::Tuple(mut exprs) => {
@@ -262,42 +699,371 @@ fn reduce_stack(stack: &mut Vec<Option<(Expression, PartOfSpeech)>>) {
                     _ => Expression::Tuple(vec![second, first]),
                 };
 
-                stack.push(Some((result, Noun)));
+                stack.push(Some((result, Noun, cover(&first_extent, &second_extent))));
             }),
 
             stack![sv, v1, v1] => lookahead!(stack, {
-                bin_impl_lr!(stack, Builtin::Compose, Verb(Unary));
+                bin_impl_lr!(stack, implicit, Builtin::Compose, Verb(Unary));
             }),
 
             stack![sv, v2, n] => lookahead!(stack, {
-                bin_impl_lr!(stack, Builtin::PartialApplicationRight, Verb(Unary));
+                bin_impl_lr!(
+                    stack,
+                    implicit,
+                    Builtin::PartialApplicationRight,
+                    Verb(Unary)
+                );
             }),
 
             stack![sv, n, v2] => lookahead!(stack, {
-                bin_impl_rl!(stack, Builtin::PartialApplicationLeft, Verb(Unary));
+                bin_impl_rl!(
+                    stack,
+                    implicit,
+                    Builtin::PartialApplicationLeft,
+                    Verb(Unary)
+                );
             }),
 
             stack![sv, v2, v1] => lookahead!(stack, {
-                bin_impl_lr!(stack, Builtin::ComposeRight, Verb(Binary));
+                bin_impl_lr!(stack, implicit, Builtin::ComposeRight, Verb(Binary));
             }),
 
             stack![sv, v1, v2] => lookahead!(stack, {
-                bin_impl_rl!(stack, Builtin::ComposeLeft, Verb(Binary));
+                bin_impl_rl!(stack, implicit, Builtin::ComposeLeft, Verb(Binary));
             }),
 
-            _ => break,
+            _ => return Ok(()),
         }
     }
 }
 
+fn top_level_allocator() -> Rc<RefCell<Allocator>> {
+    let first_id = crate::prelude::PRELUDE.len() as Identifier;
+    Rc::new(RefCell::new(Allocator::starting_at(first_id)))
+}
+
 pub(super) fn just_parse(
     statements: Vec<Statement>,
 ) -> Result<(Expression, PartOfSpeech), ParseError> {
-    let allocator = Rc::new(RefCell::new(Allocator::new()));
-    match BlockParsnip::new(allocator, statements).parse()? {
-        ParseResult::Complete(expr, pos) => Ok((expr, pos)),
-        ParseResult::PendingName(_) | ParseResult::PollingName(_) | ParseResult::PendingId(_) => {
-            panic!("partial parse")
+    parse_top_level(&mut BlockParsnip::new(top_level_allocator(), statements))
+}
+
+// The top level of a program where every expression statement is a result,
+// not just the last one, like the cells of a notebook.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Script {
+    // including the results, which are all named _
+    pub assignments: HashMap<RichIdentifier, Expression>,
+    // in the order that they appear in the source
+    pub results: Vec<RichIdentifier>,
+}
+
+pub(super) fn parse_script(statements: Vec<Statement>) -> Result<Script, ParseError> {
+    let mut block = BlockParsnip::new(top_level_allocator(), statements);
+    block.keep_results = true;
+    let assignments = match parse_top_level(&mut block)? {
+        (Expression::Compound(assignments, _), _) => assignments,
+        (expr, _) => panic!("block parsed to {}", expr),
+    };
+    let mut results = assignments
+        .keys()
+        .filter(|id| id.name == "_")
+        .cloned()
+        .collect::<Vec<_>>();
+    results.sort_by_key(|id| id.id);
+    Ok(Script {
+        assignments,
+        results,
+    })
+}
+
+// Like just_parse, but also returns the symbols of everything that resolved,
+// even if the parse as a whole failed.
+pub(super) fn parse_with_symbols(
+    statements: Vec<Statement>,
+) -> (Result<(Expression, PartOfSpeech), ParseError>, Symbols) {
+    let mut block = BlockParsnip::new(top_level_allocator(), statements);
+    let result = parse_top_level(&mut block);
+    (result, block.symbols().sorted())
+}
+
+// Like parse_with_symbols, but reuses the parses of top-level statements from
+// the last time that this cache was used, as long as nothing they depend on has
+// changed. The locations in each statement are relative to its origin, so a
+// statement that only moved is still the same statement.
+pub(super) fn parse_incrementally(
+    statements: Vec<(Statement, Origin)>,
+    cache: &mut ParseCache,
+) -> (Result<(Expression, PartOfSpeech), ParseError>, Symbols) {
+    let mut block = BlockParsnip::empty(top_level_allocator());
+    let mut recordings = vec![];
+    cache.reparsed = 0;
+    let (statements, origins): (Vec<_>, Vec<_>) = statements.into_iter().unzip();
+    let (mut declarations, defined) = pair_declarations(&statements);
+    for (i, (mut statement, origin)) in statements.into_iter().zip(origins).enumerate() {
+        if defined.contains(&i) {
+            continue;
+        }
+        let declarations = declarations.remove(&i).unwrap_or_default();
+        let key = (statement.clone(), declarations.clone());
+        statement
+            .each_location(&mut |location| *location = location.moved(Origin::default(), origin));
+        let ops_before = block.unblocked.len();
+        block.begin(statement, declarations);
+        // statements with invalid signatures fail without starting an
+        // operation, and destructuring assignments start more than one
+        let ops = block.unblocked.split_off(ops_before);
+        if ops.is_empty() {
+            cache.reparsed += 1;
+        }
+        for op in ops {
+            let diverged = Rc::new(Cell::new(true));
+            let inner: Box<dyn Parsnip> = match cache.entries.remove(&op.id) {
+                Some(entry) if entry.key == key => {
+                    diverged.set(false);
+                    Box::new(Replay {
+                        transcript: entry.transcript,
+                        position: 0,
+                        symbols: entry.symbols.moved(entry.origin, origin),
+                        diverged: Rc::clone(&diverged),
+                        inner: op.state,
+                    })
+                }
+                _ => op.state,
+            };
+            let transcript = Rc::new(RefCell::new(Some(vec![])));
+            let symbols = Rc::new(RefCell::new(Symbols::default()));
+            let recorder = Recorder {
+                inner,
+                transcript: Rc::clone(&transcript),
+                symbols: Rc::clone(&symbols),
+            };
+            block
+                .unblocked
+                .push(ParseOperation::new(op.id, Box::new(recorder)));
+            recordings.push((op.id, key.clone(), origin, transcript, symbols, diverged));
+        }
+    }
+    block.unblocked.reverse();
+
+    let result = parse_top_level(&mut block);
+
+    cache.entries.clear();
+    for (id, key, origin, transcript, symbols, diverged) in recordings {
+        if diverged.get() {
+            cache.reparsed += 1;
+        }
+        if let Some(transcript) = transcript.borrow_mut().take() {
+            if let Some(Event::Parsed(ParseResult::Complete(_, _))) = transcript.last() {
+                let entry = CacheEntry {
+                    key,
+                    origin,
+                    transcript,
+                    symbols: symbols.take(),
+                };
+                cache.entries.insert(id, entry);
+            }
+        }
+    }
+    (result, block.symbols().sorted())
+}
+
+fn parse_top_level(block: &mut BlockParsnip) -> Result<(Expression, PartOfSpeech), ParseError> {
+    loop {
+        match block.parse()? {
+            ParseResult::Complete(expr, pos) => return Ok((expr, pos)),
+            ParseResult::PollingName(name) => match crate::prelude::lookup(&name) {
+                Some((id, pos)) => block.provide(id, pos),
+                None => block.not_yet_known(&name),
+            },
+            // nothing has claimed this name, and now nothing ever will
+            ParseResult::PendingName(name) => {
+                let prelude = crate::prelude::PRELUDE.iter().map(|(name, _)| *name);
+                block.unbound(&name, &prelude.map(String::from).collect::<Vec<_>>());
+            }
+            ParseResult::PendingId(_) => panic!("partial parse"),
+        }
+    }
+}
+
+// Everything that a parse operation asked for and was told, in order. Parsing
+// is deterministic, so an operation that begins from the same statement and
+// gets the same answers will produce the same result.
+#[derive(Clone)]
+enum Event {
+    Parsed(ParseResult),
+    NotYetKnown(String),
+    Provided(RichIdentifier, PartOfSpeech),
+}
+
+type Transcript = Vec<Event>;
+
+// The transcripts of top-level statements that parsed successfully, keyed by
+// the identifier of their assignment. A statement's identifier only changes
+// when an assignment is added or removed above it, so most edits only
+// invalidate the statement that changed -- and anything whose parse depended
+// on its part of speech.
+#[derive(Default)]
+pub(super) struct ParseCache {
+    entries: HashMap<Identifier, CacheEntry>,
+    // the number of top-level statements that could not be replayed from the
+    // cache during the last parse
+    pub(super) reparsed: usize,
+}
+
+struct CacheEntry {
+    // the statement, along with its separate declarations
+    key: (Statement, Declarations),
+    // where the statement was, which its symbols are relative to
+    origin: Origin,
+    transcript: Transcript,
+    symbols: Symbols,
+}
+
+// Records the transcript of the operation that it wraps, and the symbols that
+// it resolved. We don't cache failures, so anything to do with failure
+// discards the transcript.
+struct Recorder {
+    inner: Box<dyn Parsnip>,
+    transcript: Rc<RefCell<Option<Transcript>>>,
+    symbols: Rc<RefCell<Symbols>>,
+}
+
+impl Recorder {
+    fn record(&self, event: Event) {
+        if let Some(transcript) = self.transcript.borrow_mut().as_mut() {
+            transcript.push(event);
+        }
+    }
+
+    fn discard(&self) {
+        *self.transcript.borrow_mut() = None;
+    }
+}
+
+impl Parsnip for Recorder {
+    fn not_yet_known(&mut self, name: &String) {
+        self.record(Event::NotYetKnown(name.clone()));
+        self.inner.not_yet_known(name)
+    }
+
+    fn provide(&mut self, id: RichIdentifier, pos: PartOfSpeech) {
+        self.record(Event::Provided(id.clone(), pos));
+        self.inner.provide(id, pos)
+    }
+
+    fn fail(&mut self, id: RichIdentifier, error: ParseError) {
+        self.discard();
+        self.inner.fail(id, error)
+    }
+
+    fn unbound(&mut self, name: &str, in_scope: &[String]) {
+        self.discard();
+        self.inner.unbound(name, in_scope)
+    }
+
+    fn parse(&mut self) -> Result<ParseResult, ParseError> {
+        let result = self.inner.parse();
+        match &result {
+            Ok(result) => self.record(Event::Parsed(result.clone())),
+            Err(_) => self.discard(),
+        }
+        result
+    }
+
+    fn symbols(&mut self) -> Symbols {
+        let symbols = self.inner.symbols();
+        self.symbols.borrow_mut().extend(symbols.clone());
+        symbols
+    }
+}
+
+// Answers for an operation from its old transcript, without parsing anything,
+// for as long as it's told the same things it was told last time. As soon as
+// something is different, it catches the real operation up to the same point
+// and hands everything off to it.
+struct Replay {
+    transcript: Transcript,
+    position: usize,
+    // the symbols from the old parse, moved to where the statement is now
+    symbols: Symbols,
+    diverged: Rc<Cell<bool>>,
+    inner: Box<dyn Parsnip>,
+}
+
+impl Replay {
+    // Advances past the next event if it matches, otherwise diverges.
+    fn expect(&mut self, matches: impl Fn(&Event) -> bool) -> bool {
+        if !self.diverged.get() && self.transcript.get(self.position).is_some_and(matches) {
+            self.position += 1;
+            return true;
+        }
+        self.diverge();
+        false
+    }
+
+    fn diverge(&mut self) {
+        if self.diverged.replace(true) {
+            return;
+        }
+        for event in &self.transcript[..self.position] {
+            match event {
+                Event::Parsed(_) => {
+                    // this parsed successfully last time, and it will again
+                    self.inner.parse().ok().unwrap();
+                }
+                Event::NotYetKnown(name) => self.inner.not_yet_known(name),
+                Event::Provided(id, pos) => self.inner.provide(id.clone(), *pos),
+            }
+        }
+    }
+}
+
+impl Parsnip for Replay {
+    fn not_yet_known(&mut self, name: &String) {
+        if !self.expect(|event| matches!(event, Event::NotYetKnown(old) if old == name)) {
+            self.inner.not_yet_known(name)
+        }
+    }
+
+    fn provide(&mut self, id: RichIdentifier, pos: PartOfSpeech) {
+        let same_answer = |event: &Event| {
+            matches!(event, Event::Provided(old_id, old_pos)
+                if old_id.id == id.id && old_id.name == id.name && *old_pos == pos)
+        };
+        if !self.expect(same_answer) {
+            self.inner.provide(id, pos)
+        }
+    }
+
+    fn fail(&mut self, id: RichIdentifier, error: ParseError) {
+        self.diverge();
+        self.inner.fail(id, error)
+    }
+
+    fn unbound(&mut self, name: &str, in_scope: &[String]) {
+        self.diverge();
+        self.inner.unbound(name, in_scope)
+    }
+
+    fn parse(&mut self) -> Result<ParseResult, ParseError> {
+        if !self.diverged.get() {
+            if let Some(Event::Parsed(result)) = self.transcript.get(self.position) {
+                self.position += 1;
+                return Ok(result.clone());
+            }
+        }
+        self.diverge();
+        self.inner.parse()
+    }
+
+    // an operation that's never diverged hasn't parsed anything, so the
+    // symbols are the ones that it found last time
+    fn symbols(&mut self) -> Symbols {
+        if self.diverged.get() {
+            self.inner.symbols()
+        } else {
+            std::mem::take(&mut self.symbols)
         }
     }
 }
@@ -307,14 +1073,34 @@ pub(super) fn just_parse(
 trait Parsnip {
     fn not_yet_known(&mut self, name: &String);
     fn provide(&mut self, id: RichIdentifier, pos: PartOfSpeech);
+    // Like provide, but for a reference to something that failed to parse.
+    fn fail(&mut self, id: RichIdentifier, error: ParseError);
+    // Called when a name we're pending on isn't defined in any enclosing
+    // scope. in_scope is every name defined in the enclosing scopes.
+    fn unbound(&mut self, name: &str, in_scope: &[String]);
     fn parse(&mut self) -> Result<ParseResult, ParseError>;
+    // Takes the definitions and references that this has resolved so far.
+    fn symbols(&mut self) -> Symbols;
 }
 
-struct ExpressionParsnip(Vec<ParseFrame>);
+struct ExpressionParsnip {
+    frames: Vec<ParseFrame>,
+    failure: Option<ParseError>,
+    // the identifier that we're waiting to find out about
+    pending: Option<Name>,
+    references: Vec<Symbol>,
+    implicit: Vec<Implicit>,
+}
 
 impl ExpressionParsnip {
     fn new(terms: Vec<Term>) -> Self {
-        ExpressionParsnip(vec![ParseFrame::new(terms, identity)])
+        ExpressionParsnip {
+            frames: vec![ParseFrame::new(terms, identity)],
+            failure: None,
+            pending: None,
+            references: vec![],
+            implicit: vec![],
+        }
     }
 }
 
@@ -324,35 +1110,88 @@ impl Parsnip for ExpressionParsnip {
     }
 
     fn provide(&mut self, id: RichIdentifier, pos: PartOfSpeech) {
-        let top_frame = self.0.last_mut().unwrap();
+        let extent = self.pending.as_ref().and_then(extent_of);
+        if let Some(name) = self.pending.take() {
+            self.references.push(Symbol {
+                id: id.id,
+                name: id.name.clone(),
+                location: name.location,
+                pos: Some(pos),
+            });
+        }
+        let top_frame = self.frames.last_mut().unwrap();
         top_frame
             .stack
-            .push(Some((Expression::Identifier(id), pos)));
+            .push(Some((Expression::Identifier(id), pos, extent)));
+    }
+
+    // an expression can't continue without knowing the part of speech of
+    // everything it refers to
+    fn fail(&mut self, id: RichIdentifier, error: ParseError) {
+        if let Some(name) = self.pending.take() {
+            self.references.push(Symbol {
+                id: id.id,
+                name: id.name,
+                location: name.location,
+                pos: None,
+            });
+        }
+        self.failure = Some(error);
+    }
+
+    fn unbound(&mut self, name: &str, in_scope: &[String]) {
+        let location = self.pending.take().unwrap().location;
+        let suggestions = suggestions(name, in_scope.iter().map(String::as_str));
+        self.failure = Some(ParseError::UnboundName(
+            name.to_string(),
+            location,
+            suggestions,
+        ));
+    }
+
+    fn symbols(&mut self) -> Symbols {
+        Symbols {
+            definitions: vec![],
+            references: std::mem::take(&mut self.references),
+            implicit: std::mem::take(&mut self.implicit),
+        }
     }
 
     fn parse(&mut self) -> Result<ParseResult, ParseError> {
-        let call_stack = &mut self.0;
+        if let Some(error) = self.failure.take() {
+            return Err(error);
+        }
+        let call_stack = &mut self.frames;
         loop {
             let frame = call_stack.last_mut().unwrap();
 
-            reduce_stack(&mut frame.stack);
+            reduce_stack(&mut frame.stack, &mut self.implicit)?;
 
             match frame.input.pop() {
                 None => {
                     if frame.end_reached {
                         let frame = call_stack.pop().unwrap();
-                        let without_sentinels =
-                            frame.stack.into_iter().flatten().collect::<Vec<_>>();
-                        let (expr, pos) = match without_sentinels.len() {
-                            0 => Ok((Expression::Tuple(vec![]), Noun)),
-                            1 => Ok(without_sentinels.into_iter().next().unwrap()),
-                            _ => Err(ParseError::DidNotFullyReduce(without_sentinels)),
+                        let mut without_sentinels = frame
+                            .stack
+                            .into_iter()
+                            .flatten()
+                            .map(|(expr, pos, extent)| (finish_tuple(expr), pos, extent))
+                            .collect::<Vec<_>>();
+                        let (expr, pos, extent) = match without_sentinels.len() {
+                            0 => Ok((Expression::Tuple(vec![]), Noun, None)),
+                            1 => Ok(without_sentinels.pop().unwrap()),
+                            _ => Err(ParseError::DidNotFullyReduce(
+                                without_sentinels
+                                    .into_iter()
+                                    .map(|(expr, pos, _)| (expr, pos))
+                                    .collect(),
+                            )),
                         }?;
                         let expr = (frame.finish)(expr, pos)?;
 
                         match call_stack.last_mut() {
                             None => return Ok(ParseResult::Complete(expr, pos)),
-                            Some(next) => next.stack.push(Some((expr, pos))),
+                            Some(next) => next.stack.push(Some((expr, pos, extent))),
                         }
                     } else {
                         frame.end_reached = true;
@@ -361,46 +1200,295 @@ impl Parsnip for ExpressionParsnip {
                 }
 
                 Some(term) => match term {
-                    Term::NumericLiteral(num) => frame
-                        .stack
-                        .push(Some((Expression::NumericLiteral(num), Noun))),
-                    Term::Coefficient(num) => frame.stack.push(Some((
-                        Expression::unary(
-                            Expression::Implicit(Builtin::Scale),
-                            Expression::NumericLiteral(num),
-                        ),
-                        Verb(Arity::Unary),
+                    Term::NumericLiteral(num) => frame.stack.push(Some((
+                        Expression::NumericLiteral(num.name.clone()),
+                        Noun,
+                        extent_of(&num),
                     ))),
-                    Term::Identifier(id) => return Ok(ParseResult::PendingName(id)),
-                    Term::Parens(terms) => call_stack.push(ParseFrame::new(terms, wrap_parens)),
-                    Term::Brackets(terms) => call_stack.push(ParseFrame::new(terms, wrap_brackets)),
+                    Term::Coefficient(num, token) => {
+                        let extent = extent_of(&token);
+                        self.implicit.push(Implicit {
+                            extent: extent.clone().unwrap(),
+                            builtin: Builtin::Scale,
+                        });
+                        frame.stack.push(Some((
+                            Expression::unary(
+                                Expression::Implicit(Builtin::Scale),
+                                Expression::NumericLiteral(num),
+                            ),
+                            Verb(Arity::Unary),
+                            extent,
+                        )))
+                    }
+                    Term::UnknownOperator(soup, splits) => {
+                        return Err(ParseError::UnknownOperator(
+                            soup.name,
+                            soup.location,
+                            splits,
+                        ))
+                    }
+                    Term::Identifier(name) => {
+                        let result = ParseResult::PendingName(name.name.clone());
+                        self.pending = Some(name);
+                        return Ok(result);
+                    }
+                    Term::Parens(terms, _) => call_stack.push(ParseFrame::new(terms, wrap_parens)),
+                    Term::Brackets(terms, _) => {
+                        call_stack.push(ParseFrame::new(terms, wrap_brackets))
+                    }
                 },
             };
         }
     }
 }
 
-struct ParseOperation {
-    id: Identifier,
-    state: Box<dyn Parsnip>,
+// Wraps the parse of an assignment's body, checking the result against the
+// declared part of speech. Names that refer to the definition's parameters are
+// answered here, before they can escape to the enclosing block.
+struct SignatureParsnip {
+    params: Vec<(RichIdentifier, PartOfSpeech)>,
+    result: PartOfSpeech,
+    pos: PartOfSpeech,
+    body: Box<dyn Parsnip>,
 }
 
-impl ParseOperation {
-    fn new(id: Identifier, state: Box<dyn Parsnip>) -> Self {
-        ParseOperation { id, state }
+impl SignatureParsnip {
+    fn lookup_param(&self, name: &str) -> Option<(RichIdentifier, PartOfSpeech)> {
+        self.params
+            .iter()
+            .rev()
+            .find(|(param, _)| param.name == name)
+            .cloned()
+    }
+
+    fn finish(&self, expr: Expression, pos: PartOfSpeech) -> Result<ParseResult, ParseError> {
+        if pos != self.result {
+            Err(ParseError::PartOfSpeechMismatch(self.result, pos))
+        } else if self.params.is_empty() {
+            Ok(ParseResult::Complete(expr, pos))
+        } else {
+            Ok(ParseResult::Complete(
+                Expression::Lambda(self.params.clone(), Box::new(expr), self.pos),
+                self.pos,
+            ))
+        }
     }
 }
 
-struct BlockParsnip {
-    name_to_ids: HashMap<String, Vec<Identifier>>,
-    id_to_name: HashMap<Identifier, String>,
+impl Parsnip for SignatureParsnip {
+    fn not_yet_known(&mut self, name: &String) {
+        self.body.not_yet_known(name)
+    }
+
+    fn provide(&mut self, id: RichIdentifier, pos: PartOfSpeech) {
+        self.body.provide(id, pos)
+    }
+
+    fn fail(&mut self, id: RichIdentifier, error: ParseError) {
+        self.body.fail(id, error)
+    }
+
+    fn unbound(&mut self, name: &str, in_scope: &[String]) {
+        let params = self.params.iter().map(|(param, _)| param.name.clone());
+        let in_scope = in_scope.iter().cloned().chain(params).collect::<Vec<_>>();
+        self.body.unbound(name, &in_scope)
+    }
+
+    fn symbols(&mut self) -> Symbols {
+        self.body.symbols()
+    }
+
+    fn parse(&mut self) -> Result<ParseResult, ParseError> {
+        loop {
+            let result = self.body.parse()?;
+            let param = match &result {
+                ParseResult::PendingName(name) | ParseResult::PollingName(name) => {
+                    self.lookup_param(name)
+                }
+                ParseResult::Complete(_, _) | ParseResult::PendingId(_) => None,
+            };
+            match (param, result) {
+                (Some((id, pos)), _) => self.body.provide(id, pos),
+                (None, ParseResult::Complete(expr, pos)) => return self.finish(expr, pos),
+                (None, result) => return Ok(result),
+            }
+        }
+    }
+}
+
+// Parses the condition and then both branches of a conditional, one after
+// another.
+struct ConditionalParsnip {
+    parts: Vec<Box<dyn Parsnip>>,
+    results: Vec<(Expression, PartOfSpeech)>,
+}
+
+impl ConditionalParsnip {
+    fn new(
+        condition: Box<dyn Parsnip>,
+        consequent: Box<dyn Parsnip>,
+        alternative: Box<dyn Parsnip>,
+    ) -> Self {
+        ConditionalParsnip {
+            parts: vec![condition, consequent, alternative],
+            results: vec![],
+        }
+    }
+
+    fn current(&mut self) -> &mut Box<dyn Parsnip> {
+        &mut self.parts[self.results.len()]
+    }
+
+    fn finish(&mut self) -> Result<ParseResult, ParseError> {
+        // branches that consist of a single expression don't need to be
+        // wrapped in a let
+        fn unwrap_block(expr: Expression) -> Expression {
+            match expr {
+                Expression::Compound(bindings, result) if bindings.is_empty() => *result,
+                expr => expr,
+            }
+        }
+        let mut results = self.results.drain(..);
+        let (condition, condition_pos) = results.next().unwrap();
+        let (consequent, consequent_pos) = results.next().unwrap();
+        let (alternative, alternative_pos) = results.next().unwrap();
+        if condition_pos != Noun {
+            return Err(ParseError::PartOfSpeechMismatch(Noun, condition_pos));
+        }
+        if consequent_pos != alternative_pos {
+            return Err(ParseError::PartOfSpeechMismatch(
+                consequent_pos,
+                alternative_pos,
+            ));
+        }
+        Ok(ParseResult::Complete(
+            Expression::Conditional(
+                Box::new(condition),
+                Box::new(unwrap_block(consequent)),
+                Box::new(unwrap_block(alternative)),
+            ),
+            consequent_pos,
+        ))
+    }
+}
+
+impl Parsnip for ConditionalParsnip {
+    fn not_yet_known(&mut self, name: &String) {
+        self.current().not_yet_known(name)
+    }
+
+    fn provide(&mut self, id: RichIdentifier, pos: PartOfSpeech) {
+        self.current().provide(id, pos)
+    }
+
+    fn fail(&mut self, id: RichIdentifier, error: ParseError) {
+        self.current().fail(id, error)
+    }
+
+    fn unbound(&mut self, name: &str, in_scope: &[String]) {
+        self.current().unbound(name, in_scope)
+    }
+
+    fn symbols(&mut self) -> Symbols {
+        let mut symbols = Symbols::default();
+        for part in &mut self.parts {
+            symbols.extend(part.symbols());
+        }
+        symbols
+    }
+
+    fn parse(&mut self) -> Result<ParseResult, ParseError> {
+        loop {
+            match self.current().parse()? {
+                ParseResult::Complete(expr, pos) => {
+                    self.results.push((expr, pos));
+                    if self.results.len() == self.parts.len() {
+                        return self.finish();
+                    }
+                }
+                result => return Ok(result),
+            }
+        }
+    }
+}
+
+// One of the names bound by a destructuring assignment. It waits for the tuple
+// to parse, and then it's just an element of it.
+struct ElementParsnip {
+    tuple: String,
+    index: usize,
+    length: usize,
+    tuple_id: Option<Result<RichIdentifier, ParseError>>,
+}
+
+impl Parsnip for ElementParsnip {
+    fn not_yet_known(&mut self, _name: &String) {
+        panic!("the tuple is always defined right before its elements")
+    }
+
+    fn provide(&mut self, id: RichIdentifier, _pos: PartOfSpeech) {
+        self.tuple_id = Some(Ok(id));
+    }
+
+    fn fail(&mut self, _id: RichIdentifier, error: ParseError) {
+        self.tuple_id = Some(Err(error));
+    }
+
+    fn unbound(&mut self, _name: &str, _in_scope: &[String]) {
+        panic!("the tuple is always defined right before its elements")
+    }
+
+    fn symbols(&mut self) -> Symbols {
+        Symbols::default()
+    }
+
+    // the tuple's signature already made sure that it's a noun
+    fn parse(&mut self) -> Result<ParseResult, ParseError> {
+        match self.tuple_id.take() {
+            None => Ok(ParseResult::PendingName(self.tuple.clone())),
+            Some(Ok(id)) => Ok(ParseResult::Complete(
+                Expression::Element(
+                    Box::new(Expression::Identifier(id)),
+                    self.index,
+                    self.length,
+                ),
+                Noun,
+            )),
+            Some(Err(error)) => Err(error),
+        }
+    }
+}
+
+struct ParseOperation {
+    id: Identifier,
+    state: Box<dyn Parsnip>,
+}
+
+impl ParseOperation {
+    fn new(id: Identifier, state: Box<dyn Parsnip>) -> Self {
+        ParseOperation { id, state }
+    }
+}
+
+struct BlockParsnip {
+    name_to_ids: HashMap<String, Vec<Identifier>>,
+    id_to_name: HashMap<Identifier, String>,
+    locations: HashMap<Identifier, Location>,
 
     polling_name: HashMap<String, Vec<ParseOperation>>,
-    blocked_on_name: HashMap<String, Vec<ParseOperation>>,
-    blocked_on_id: HashMap<Identifier, Vec<ParseOperation>>,
+    // these are ordered so that the order in which we ask for names and
+    // identifiers is deterministic, which ParseCache relies on
+    blocked_on_name: BTreeMap<String, Vec<ParseOperation>>,
+    blocked_on_id: BTreeMap<Identifier, Vec<ParseOperation>>,
     complete: HashMap<Identifier, (Expression, PartOfSpeech)>,
     failed: HashMap<Identifier, ParseError>,
     unblocked: Vec<ParseOperation>,
+    declared: HashMap<Identifier, PartOfSpeech>,
+    // the symbols of everything that has finished parsing, one way or another
+    symbols: Symbols,
+    // At the top level of a script, every result is kept as an assignment,
+    // and the result of the block refers to the last one.
+    keep_results: bool,
 
     allocator: Rc<RefCell<Allocator>>,
 }
@@ -410,8 +1498,12 @@ struct Allocator {
 }
 
 impl Allocator {
+    #[cfg(test)]
     fn new() -> Self {
-        Allocator { current: 0 }
+        Self::starting_at(0)
+    }
+    fn starting_at(current: Identifier) -> Self {
+        Allocator { current }
     }
     fn next(&mut self) -> Identifier {
         let x = self.current;
@@ -424,25 +1516,18 @@ enum LookupResult<'a> {
     Unknown,
     Pending(Identifier),
     Failed(Identifier, &'a ParseError),
+    Declared(Identifier, PartOfSpeech),
     Complete(Identifier, &'a Expression, PartOfSpeech),
 }
 
 impl BlockParsnip {
     fn new(allocator: Rc<RefCell<Allocator>>, statements: Vec<Statement>) -> Self {
-        let mut this = BlockParsnip {
-            name_to_ids: HashMap::new(),
-            id_to_name: HashMap::new(),
-            allocator,
-            polling_name: HashMap::new(),
-            blocked_on_name: HashMap::new(),
-            blocked_on_id: HashMap::new(),
-            complete: HashMap::new(),
-            failed: HashMap::new(),
-            unblocked: vec![],
-        };
-
-        for statement in statements {
-            this.begin(statement);
+        let mut this = Self::empty(allocator);
+        let (mut declarations, defined) = pair_declarations(&statements);
+        for (i, statement) in statements.into_iter().enumerate() {
+            if !defined.contains(&i) {
+                this.begin(statement, declarations.remove(&i).unwrap_or_default());
+            }
         }
         // We need to begin elements from top-to-bottom, but every time we begin
         // something we push it onto a stack. But I think it will be more
@@ -456,29 +1541,182 @@ impl BlockParsnip {
         this
     }
 
-    fn begin(&mut self, statement: Statement) {
+    fn empty(allocator: Rc<RefCell<Allocator>>) -> Self {
+        BlockParsnip {
+            name_to_ids: HashMap::new(),
+            id_to_name: HashMap::new(),
+            locations: HashMap::new(),
+            allocator,
+            polling_name: HashMap::new(),
+            blocked_on_name: BTreeMap::new(),
+            blocked_on_id: BTreeMap::new(),
+            complete: HashMap::new(),
+            failed: HashMap::new(),
+            unblocked: vec![],
+            declared: HashMap::new(),
+            symbols: Symbols::default(),
+            keep_results: false,
+        }
+    }
+
+    fn begin(&mut self, statement: Statement, declarations: Declarations) {
+        let mut declarations = declarations.into_iter();
+        let declaration = declarations.next().flatten();
         match statement {
-            Statement::SimpleAssignment(name, terms) => {
-                let id = self.learn_name(name);
-                self.unblocked.push(ParseOperation::new(
-                    id,
-                    Box::new(ExpressionParsnip::new(terms)),
-                ));
+            Statement::SimpleAssignment(target, signature, terms) => {
+                let id = self.learn_target(target);
+                let body = Box::new(ExpressionParsnip::new(terms));
+                self.begin_assignment(id, signature, declaration, body);
             }
-            Statement::CompoundAssignment(name, statements) => {
-                let id = self.learn_name(name);
-                self.unblocked.push(ParseOperation::new(
-                    id,
-                    Box::new(BlockParsnip::new(Rc::clone(&self.allocator), statements)),
-                ));
+            Statement::CompoundAssignment(target, signature, statements) => {
+                let block = BlockParsnip::new(Rc::clone(&self.allocator), statements);
+                let id = self.learn_target(target);
+                self.begin_assignment(id, signature, declaration, Box::new(block));
             }
             Statement::Expression(terms) => {
                 // TODO: another case where we could reference a constant or something
-                self.begin(Statement::SimpleAssignment("_".to_string(), terms))
+                let id = self.learn_name("_".to_string());
+                let body = Box::new(ExpressionParsnip::new(terms));
+                self.begin_assignment(id, None, None, body);
+            }
+            // declarations that declare something are skipped entirely, so
+            // this one doesn't have a definition, or was declared again before
+            // it had one. it gets an identifier so that it fails anything that
+            // refers to it
+            Statement::Declaration(target, _) => {
+                let id = self.learn_target(target);
+                let error = match declaration {
+                    Some(_) => ParseError::DuplicateDeclaration,
+                    None => ParseError::DeclarationWithoutDefinition,
+                };
+                self.failed(id, error);
+            }
+            // The tuple gets an assignment of its own, named after the
+            // pattern, since that's what it looks like in error messages.
+            // Nothing in the source can refer to it by that name.
+            Statement::Destructuring(targets, terms) => {
+                let names = targets.iter().map(|target| target.name.as_str());
+                let tuple = format!("({})", names.collect::<Vec<_>>().join(" "));
+                let id = self.learn_name(tuple.clone());
+                let body = Box::new(ExpressionParsnip::new(terms));
+                let signature = Signature {
+                    params: vec![],
+                    result: Noun,
+                };
+                self.begin_assignment(id, Some(signature.clone()), None, body);
+                let length = targets.len();
+                let declarations = std::iter::once(declaration)
+                    .chain(declarations)
+                    .chain(std::iter::repeat(None));
+                for ((index, target), declaration) in
+                    targets.into_iter().enumerate().zip(declarations)
+                {
+                    let id = self.learn_target(target);
+                    let parsnip = ElementParsnip {
+                        tuple: tuple.clone(),
+                        index,
+                        length,
+                        tuple_id: None,
+                    };
+                    // Every element of a tuple is a noun, so a declaration
+                    // has to agree with that.
+                    let signature = declaration.as_ref().map(|_| signature.clone());
+                    self.begin_assignment(id, signature, declaration, Box::new(parsnip));
+                }
             }
+            Statement::Conditional(terms, consequent, _, alternative) => {
+                let id = self.learn_name("_".to_string());
+                let parsnip = ConditionalParsnip::new(
+                    Box::new(ExpressionParsnip::new(terms)),
+                    Box::new(BlockParsnip::new(Rc::clone(&self.allocator), consequent)),
+                    Box::new(BlockParsnip::new(Rc::clone(&self.allocator), alternative)),
+                );
+                self.unblocked
+                    .push(ParseOperation::new(id, Box::new(parsnip)));
+            }
+        }
+    }
+
+    fn learn_target(&mut self, target: Name) -> Identifier {
+        let id = self.learn_name(target.name);
+        self.locations.insert(id, target.location);
+        id
+    }
+
+    // records the definition of a named assignment, once we know how it went
+    fn define(&mut self, id: Identifier, pos: Option<PartOfSpeech>) {
+        if let Some(location) = self.locations.get(&id) {
+            self.symbols.definitions.push(Symbol {
+                id,
+                name: self.name_of_id(&id),
+                location: *location,
+                pos,
+            });
         }
     }
 
+    // A separate declaration works just like a signature without parameters,
+    // unless the assignment has a signature of its own, in which case they
+    // have to agree.
+    fn begin_assignment(
+        &mut self,
+        id: Identifier,
+        signature: Option<Signature>,
+        declaration: Option<Signature>,
+        body: Box<dyn Parsnip>,
+    ) {
+        let declared = match declaration.map(|declaration| declaration.part_of_speech()) {
+            None => None,
+            Some(Ok(pos)) => Some(pos),
+            Some(Err(e)) => return self.failed(id, e),
+        };
+        let signature = match (signature, declared) {
+            (None, None) => {
+                self.unblocked.push(ParseOperation::new(id, body));
+                return;
+            }
+            (None, Some(pos)) => Signature {
+                params: vec![],
+                result: pos,
+            },
+            (Some(signature), None) => signature,
+            (Some(signature), Some(declared)) => match signature.part_of_speech() {
+                Ok(pos) if pos != declared => {
+                    return self.failed(id, ParseError::PartOfSpeechMismatch(declared, pos))
+                }
+                _ => signature,
+            },
+        };
+        let pos = match signature.part_of_speech() {
+            Ok(pos) => pos,
+            Err(e) => return self.failed(id, e),
+        };
+        self.declared.insert(id, pos);
+
+        let params = signature
+            .params
+            .into_iter()
+            .map(|(name, pos)| {
+                let param_id = self.allocator.borrow_mut().next();
+                self.symbols.definitions.push(Symbol {
+                    id: param_id,
+                    name: name.name.clone(),
+                    location: name.location,
+                    pos: Some(pos),
+                });
+                (RichIdentifier::new(param_id, name.name), pos)
+            })
+            .collect();
+        let parsnip = SignatureParsnip {
+            params,
+            result: signature.result,
+            pos,
+            body,
+        };
+        self.unblocked
+            .push(ParseOperation::new(id, Box::new(parsnip)));
+    }
+
     fn polling_name(&mut self, prereq_name: String, parse: ParseOperation) {
         self.polling_name
             .entry(prereq_name)
@@ -502,14 +1740,135 @@ impl BlockParsnip {
 
     fn failed(&mut self, id: Identifier, error: ParseError) {
         if let Some(parses) = self.blocked_on_id.remove(&id) {
-            for parse in parses {
-                self.failed.insert(parse.id, ParseError::BadReference(id));
+            let rich_id = RichIdentifier::new(id, self.name_of_id(&id));
+            let bad_reference = self.bad_reference(id, &error);
+            for mut parse in parses {
+                parse.state.fail(rich_id.clone(), bad_reference.clone());
+                self.unblocked.push(parse);
             }
         }
 
+        self.define(id, self.declared.get(&id).copied());
         assert!(self.failed.insert(id, error).is_none());
     }
 
+    fn bad_reference(&self, id: Identifier, error: &ParseError) -> ParseError {
+        match error {
+            ParseError::BadReference(_, _) => error.clone(),
+            _ => ParseError::BadReference(self.binding(id), Box::new(error.clone())),
+        }
+    }
+
+    fn binding(&self, id: Identifier) -> Binding {
+        Binding {
+            id,
+            name: self.name_of_id(&id),
+            location: self.locations.get(&id).cloned(),
+        }
+    }
+
+    // Every assignment blocked on an identifier in this block is waiting on
+    // exactly one other assignment, so following those dependencies from any
+    // of them will eventually arrive at a cycle.
+    fn find_cycle(&self) -> Option<Vec<Identifier>> {
+        let waiting_on = self
+            .blocked_on_id
+            .iter()
+            .flat_map(|(prereq_id, parses)| parses.iter().map(move |parse| (parse.id, *prereq_id)))
+            .collect::<HashMap<_, _>>();
+        let mut path = vec![];
+        let mut id = *waiting_on.keys().min()?;
+        while !path.contains(&id) {
+            path.push(id);
+            id = waiting_on[&id];
+        }
+        let mut cycle = path.split_off(path.iter().position(|x| *x == id).unwrap());
+        let start = cycle
+            .iter()
+            .position(|x| x == cycle.iter().min().unwrap())
+            .unwrap();
+        cycle.rotate_left(start);
+        Some(cycle)
+    }
+
+    // Parses everything that's unblocked until nothing is. Returns a result if
+    // this block can't make any more progress on its own.
+    fn reduce(&mut self) -> Option<Result<ParseResult, ParseError>> {
+        while let Some(mut op) = self.unblocked.pop() {
+            match op.state.parse() {
+                Err(e) => {
+                    self.symbols.extend(op.state.symbols());
+                    self.failed(op.id, e);
+                }
+                Ok(ParseResult::Complete(expr, pos)) => {
+                    self.symbols.extend(op.state.symbols());
+                    self.complete(op.id, expr, pos);
+                }
+                Ok(ParseResult::PendingId(prereq_id)) => {
+                    self.blocked_on_id(prereq_id, op);
+                }
+                Ok(ParseResult::PendingName(prereq_name)) => {
+                    if let Some(op) = self.try_providing_name(op, &prereq_name, Backwards) {
+                        self.blocked_on_name(prereq_name.clone(), op);
+                        return Some(Ok(ParseResult::PollingName(prereq_name)));
+                    }
+                }
+                Ok(ParseResult::PollingName(prereq_name)) => {
+                    if let Some(op) = self.try_providing_name(op, &prereq_name, Backwards) {
+                        self.polling_name(prereq_name.clone(), op);
+                        return Some(Ok(ParseResult::PollingName(prereq_name)));
+                    }
+                }
+            }
+        }
+        // At this point we have fully reduced ourselves.
+        //
+        // If something is blocked on name, we need to return pending.
+        //
+        // Otherwise, if something is blocked on an ID defined in a parent scope,
+        // then we need to return pending.
+        //
+        // Otherwise, if something is blocked on an ID defined in *my* scope,
+        // there's a cyclic definition, which the caller has to break.
+
+        if let Some(name) = self.blocked_on_name.keys().next() {
+            return Some(Ok(ParseResult::PendingName(name.clone())));
+        }
+
+        // TODO: a bit of denormalization would remove the need for a linear
+        // scan here
+        for id in self.blocked_on_id.keys() {
+            if !self.id_to_name.contains_key(id) {
+                return Some(Ok(ParseResult::PendingId(*id)));
+            }
+        }
+        None
+    }
+
+    // We break a cycle by failing its first assignment, which will fail
+    // everything else in the cycle (and everything that depends on it) with a
+    // BadReference. So every cycle is reported exactly once. Returns false if
+    // there are no cycles.
+    fn break_cycle(&mut self) -> bool {
+        let cycle = match self.find_cycle() {
+            Some(cycle) => cycle,
+            None => return false,
+        };
+        let first = cycle[0];
+        let prereq_id = cycle[1 % cycle.len()];
+        let parses = self.blocked_on_id.get_mut(&prereq_id).unwrap();
+        let position = parses.iter().position(|parse| parse.id == first).unwrap();
+        let mut op = parses.remove(position);
+        self.symbols.extend(op.state.symbols());
+        let parses = &self.blocked_on_id[&prereq_id];
+        if parses.is_empty() {
+            self.blocked_on_id.remove(&prereq_id);
+        }
+        let bindings = cycle.iter().map(|id| self.binding(*id)).collect();
+        self.failed(first, ParseError::CyclicAssignments(bindings));
+        true
+    }
+
     fn name_of_id(&self, id: &Identifier) -> String {
         if let Some(name) = self.id_to_name.get(id) {
             return name.clone();
@@ -525,6 +1884,7 @@ impl BlockParsnip {
                 self.unblocked.push(parse);
             }
         }
+        self.define(id, Some(pos));
         assert!(self.complete.insert(id, (expr, pos)).is_none());
     }
 
@@ -548,13 +1908,21 @@ impl BlockParsnip {
         })
     }
 
-    fn lookup_by_id(&self, id: Identifier) -> LookupResult {
+    fn lookup_by_id(&self, id: Identifier) -> LookupResult<'_> {
         if let Some((expr, pos)) = self.complete.get(&id) {
             return LookupResult::Complete(id, expr, *pos);
         }
         if let Some(error) = self.failed.get(&id) {
             return LookupResult::Failed(id, error);
         }
+        // Verbs and adverbs with a declared part of speech can be used before
+        // their definitions have been parsed. A declared noun still has to wait
+        // for its definition, so that nouns cannot be defined in terms of
+        // themselves.
+        match self.declared.get(&id) {
+            Some(Noun) | None => (),
+            Some(pos) => return LookupResult::Declared(id, *pos),
+        }
         // a more "obvious" approach would be to check the two "blocked" keys
         // for the Pending result and then panic if we never find something. but
         // that would require either linearly scanning the blocked dictionaries
@@ -577,7 +1945,7 @@ impl BlockParsnip {
         }
     }
 
-    fn lookup(&self, name: &str, as_of: Identifier, dir: Direction) -> LookupResult {
+    fn lookup(&self, name: &str, as_of: Identifier, dir: Direction) -> LookupResult<'_> {
         match self.lookup_identifier(name, as_of, dir) {
             Some(id) => self.lookup_by_id(id),
             None => LookupResult::Unknown,
@@ -614,8 +1982,17 @@ impl BlockParsnip {
                 self.blocked_on_id(prereq_id, op);
                 None
             }
-            LookupResult::Failed(prereq_id, _) => {
-                self.failed(op.id, ParseError::BadReference(prereq_id));
+            LookupResult::Failed(prereq_id, error) => {
+                let error = self.bad_reference(prereq_id, error);
+                op.state
+                    .fail(RichIdentifier::new(prereq_id, name.clone()), error);
+                self.unblocked.push(op);
+                None
+            }
+            LookupResult::Declared(prereq_id, pos) => {
+                op.state
+                    .provide(RichIdentifier::new(prereq_id, name.clone()), pos);
+                self.unblocked.push(op);
                 None
             }
             LookupResult::Complete(prereq_id, _expr, pos) => {
@@ -628,6 +2005,45 @@ impl BlockParsnip {
     }
 }
 
+// The separate declarations of the names that a statement assigns, in order.
+// Destructuring assignments assign more than one name, and any of them might
+// not be declared. A declaration's own name is declared again if another
+// declaration replaces it before it's used.
+type Declarations = Vec<Option<Signature>>;
+
+// Pairs every declaration with the assignment that it declares: the next one
+// to the same name, as long as the name isn't declared again first. Returns
+// the declarations of every statement that has any, by index, and the indices
+// of the declarations that were used.
+fn pair_declarations(statements: &[Statement]) -> (HashMap<usize, Declarations>, HashSet<usize>) {
+    let mut pending = HashMap::new();
+    let mut declarations = HashMap::new();
+    let mut defined = HashSet::new();
+    for (i, statement) in statements.iter().enumerate() {
+        let targets = match statement {
+            Statement::Declaration(target, signature) => {
+                if let Some((replaced, _)) = pending.insert(target.name.as_str(), (i, signature)) {
+                    declarations.insert(replaced, vec![Some(signature.clone())]);
+                }
+                continue;
+            }
+            Statement::SimpleAssignment(target, _, _)
+            | Statement::CompoundAssignment(target, _, _) => std::slice::from_ref(target),
+            Statement::Destructuring(targets, _) => targets.as_slice(),
+            Statement::Expression(_) | Statement::Conditional(_, _, _, _) => continue,
+        };
+        for (n, target) in targets.iter().enumerate() {
+            if let Some((declaration, signature)) = pending.remove(target.name.as_str()) {
+                declarations
+                    .entry(i)
+                    .or_insert_with(|| vec![None; targets.len()])[n] = Some(signature.clone());
+                defined.insert(declaration);
+            }
+        }
+    }
+    (declarations, defined)
+}
+
 impl Parsnip for BlockParsnip {
     fn not_yet_known(&mut self, name: &String) {
         // Now that we know that it's not present in the parent, we can check
@@ -675,65 +2091,59 @@ impl Parsnip for BlockParsnip {
         }
     }
 
+    fn fail(&mut self, id: RichIdentifier, error: ParseError) {
+        let parses = [
+            self.polling_name.remove(&id.name),
+            self.blocked_on_name.remove(&id.name),
+            self.blocked_on_id.remove(&id.id),
+        ];
+        for mut parse in parses.into_iter().flatten().flatten() {
+            parse.state.fail(id.clone(), error.clone());
+            self.unblocked.push(parse);
+        }
+    }
+
+    fn unbound(&mut self, name: &str, in_scope: &[String]) {
+        let parses = [
+            self.polling_name.remove(name),
+            self.blocked_on_name.remove(name),
+        ];
+        let in_scope = in_scope
+            .iter()
+            .chain(self.name_to_ids.keys())
+            .cloned()
+            .collect::<Vec<_>>();
+        for mut parse in parses.into_iter().flatten().flatten() {
+            parse.state.unbound(name, &in_scope);
+            self.unblocked.push(parse);
+        }
+    }
+
+    fn symbols(&mut self) -> Symbols {
+        std::mem::take(&mut self.symbols)
+    }
+
     fn parse(&mut self) -> Result<ParseResult, ParseError> {
         assert!(self.polling_name.is_empty());
 
-        while let Some(mut op) = self.unblocked.pop() {
-            match op.state.parse() {
-                Err(e) => {
-                    self.failed(op.id, e);
-                }
-                Ok(ParseResult::Complete(expr, pos)) => {
-                    self.complete(op.id, expr, pos);
-                }
-                Ok(ParseResult::PendingId(prereq_id)) => {
-                    self.blocked_on_id(prereq_id, op);
-                }
-                Ok(ParseResult::PendingName(prereq_name)) => {
-                    if let Some(op) = self.try_providing_name(op, &prereq_name, Backwards) {
-                        self.blocked_on_name(prereq_name.clone(), op);
-                        return Ok(ParseResult::PollingName(prereq_name));
-                    }
-                }
-                Ok(ParseResult::PollingName(prereq_name)) => {
-                    if let Some(op) = self.try_providing_name(op, &prereq_name, Backwards) {
-                        self.polling_name(prereq_name.clone(), op);
-                        return Ok(ParseResult::PollingName(prereq_name));
-                    }
-                }
+        loop {
+            if let Some(result) = self.reduce() {
+                return result;
+            }
+            // breaking a cycle unblocks everything that was waiting on it
+            if !self.break_cycle() {
+                break;
             }
         }
-        // At this point we have fully reduced ourselves.
-        //
-        // If any assignment failed, the whole parse failed.
-        //
-        // Otherwise, if something is blocked on name, we need to return pending.
-        //
-        // Otherwise, if something is blocked on an ID defined in a parent scope,
-        // then we need to return pending.
-        //
-        // Otherwise, if something is blocked on an ID defined in *my* scope,
-        // there's a cyclic definition and we can error immediately.
-        //
-        // Otherwise, we successfully parsed every assignment.
 
         if !self.failed.is_empty() {
-            return Err(ParseError::SubAssignmentFailed);
-        }
-
-        if let Some(name) = self.blocked_on_name.keys().next() {
-            return Ok(ParseResult::PendingName(name.clone()));
-        }
-
-        // TODO: a bit of denormalization would remove the need for a linear
-        // scan here
-        for id in self.blocked_on_id.keys() {
-            if !self.id_to_name.contains_key(id) {
-                return Ok(ParseResult::PendingId(*id));
-            }
-        }
-        if !self.blocked_on_id.is_empty() {
-            return Err(ParseError::CyclicAssignments);
+            let mut failures = self
+                .failed
+                .iter()
+                .map(|(id, error)| (self.binding(*id), error.clone()))
+                .collect::<Vec<_>>();
+            failures.sort_by_key(|(binding, _)| binding.id);
+            return Err(ParseError::SubAssignmentsFailed(failures));
         }
 
         // TODO: in order to use this to drive a repl, we need to have some way
@@ -742,8 +2152,7 @@ impl Parsnip for BlockParsnip {
         // caller decide to invoke some kind of "finalize" method to extract the
         // actual expression/POS at its discretion.
 
-        // TODO: should maybe cache this key? also do we want to allow multiple
-        // top-level statements...? is that actually desirable in any way?
+        // TODO: should maybe cache this key?
         match self.name_to_ids.get("_") {
             None => Err(ParseError::BlockWithoutResult),
             Some(ids) => {
@@ -754,7 +2163,14 @@ impl Parsnip for BlockParsnip {
                 // superior to a variant. So this mutates itself until its in
                 // sort of an invalid state -- bad things would happen if the
                 // caller continued to use the parsnip after this.
-                let (result_expr, result_pos) = self.complete.remove(ids.last().unwrap()).unwrap();
+                let last = *ids.last().unwrap();
+                let (result_expr, result_pos) = if self.keep_results {
+                    let (_, pos) = self.complete[&last];
+                    let id = RichIdentifier::new(last, "_".to_string());
+                    (Expression::Identifier(id), pos)
+                } else {
+                    self.complete.remove(&last).unwrap()
+                };
                 let assignments = self
                     .complete
                     .drain()
@@ -802,9 +2218,9 @@ mod tests {
                     let pos = match name.as_str() {
                         "+" | "*" => Verb(Arity::Binary),
                         "neg" | "sign" => Verb(Arity::Unary),
-                        "." => Adverb(Arity::Binary, Arity::Binary),
-                        "fold" => Adverb(Arity::Unary, Arity::Unary),
-                        "flip" => Adverb(Arity::Unary, Arity::Binary),
+                        "." => Adverb(Arity::Binary, Arity::Binary, ANY_OPERANDS),
+                        "fold" => Adverb(Arity::Unary, Arity::Unary, ANY_OPERANDS),
+                        "flip" => Adverb(Arity::Unary, Arity::Binary, ANY_OPERANDS),
                         "x" | "y" => Noun,
                         _ => panic!("unknown identifier"),
                     };
@@ -815,7 +2231,7 @@ mod tests {
     }
 
     fn preparse(input: &str) -> Vec<Term> {
-        let tokens = crate::tokenizer::tokenize(input);
+        let tokens = crate::tokenizer::tokenize(input).unwrap();
         let terms = crate::statement_parser::parse_expression(tokens).unwrap();
         let terms = crate::semicolons::resolve_expression(terms);
         let terms = crate::op_splitter::split_expression(terms);
@@ -949,6 +2365,24 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_element_order() {
+        // check the stored order directly, since the printer would hide it
+        fn literals(expr: &Expression) -> Vec<String> {
+            match expr {
+                Expression::Tuple(exprs) | Expression::Brackets(exprs) => {
+                    exprs.iter().flat_map(literals).collect()
+                }
+                Expression::NumericLiteral(num) => vec![num.clone()],
+                _ => panic!("unexpected expression {}", expr),
+            }
+        }
+        let expr = |input| parse_to_completion(preparse(input)).unwrap().0;
+        k9::snapshot!(literals(&expr("1 2 3")).join(" "), "1 2 3");
+        k9::snapshot!(literals(&expr("[1 2 3]")).join(" "), "1 2 3");
+        k9::snapshot!(literals(&expr("[1 2; 3 4]")).join(" "), "1 2 3 4");
+    }
+
     #[test]
     fn test_confusing_expressions() {
         k9::snapshot!(test("* 1 +"), "v2:(<comp-rhs> * (<lhs> + 1))");
@@ -1051,6 +2485,9 @@ mod tests {
             Identifier(rich_id) => Identifier(f(rich_id)),
             NumericLiteral(_) | Implicit(_) => expr.clone(),
             Parens(exprs) => Parens(Box::new(rewrite_ids(exprs, f))),
+            Element(tuple, index, length) => {
+                Element(Box::new(rewrite_ids(tuple, f)), *index, *length)
+            }
             Tuple(exprs) => Tuple(exprs.iter().map(|expr| rewrite_ids(expr, f)).collect()),
             Brackets(exprs) => Brackets(exprs.iter().map(|expr| rewrite_ids(expr, f)).collect()),
             UnaryApplication(expr1, expr2) => {
@@ -1068,6 +2505,16 @@ mod tests {
                     .collect(),
                 Box::new(rewrite_ids(expr, f)),
             ),
+            Lambda(params, body, pos) => Expression::Lambda(
+                params.iter().map(|(param, pos)| (f(param), *pos)).collect(),
+                Box::new(rewrite_ids(body, f)),
+                *pos,
+            ),
+            Conditional(condition, consequent, alternative) => Expression::Conditional(
+                Box::new(rewrite_ids(condition, f)),
+                Box::new(rewrite_ids(consequent, f)),
+                Box::new(rewrite_ids(alternative, f)),
+            ),
         }
     }
 
@@ -1131,9 +2578,8 @@ mod tests {
                         expr
                     ));
                 }
-                AssignmentStatus::Failed(ParseError::BadReference(prereq_id)) => {
-                    let prereq_name = block.name_of_id(prereq_id);
-                    let rich_prereq_id = RichIdentifier::new(*prereq_id, prereq_name);
+                AssignmentStatus::Failed(ParseError::BadReference(prereq, _)) => {
+                    let rich_prereq_id = RichIdentifier::new(prereq.id, prereq.name.clone());
                     disambiguator.see(rich_prereq_id.clone());
                     result.push_str(&format!(
                         "{} depends on failed {}",
@@ -1141,6 +2587,29 @@ mod tests {
                         disambiguator.view(&rich_prereq_id)
                     ));
                 }
+                AssignmentStatus::Failed(ParseError::CyclicAssignments(bindings)) => {
+                    let mut names = bindings
+                        .iter()
+                        .map(|binding| {
+                            let rich_id = RichIdentifier::new(binding.id, binding.name.clone());
+                            disambiguator.see(rich_id.clone());
+                            disambiguator.view(&rich_id)
+                        })
+                        .collect::<Vec<_>>();
+                    names.push(names[0].clone());
+                    result.push_str(&format!(
+                        "{} failed: cycle {}",
+                        disambiguator.view(&rich_id),
+                        names.join(" -> ")
+                    ));
+                }
+                AssignmentStatus::Failed(error @ ParseError::SubAssignmentsFailed(_)) => {
+                    result.push_str(&format!(
+                        "{} failed: {}",
+                        disambiguator.view(&rich_id),
+                        error
+                    ));
+                }
                 AssignmentStatus::Failed(error) => {
                     result.push_str(&format!(
                         "{} failed: {:?}",
@@ -1174,16 +2643,16 @@ mod tests {
         match name.as_str() {
             "+" | "*" => Some(Verb(Arity::Binary)),
             "neg" | "sign" => Some(Verb(Arity::Unary)),
-            "." => Some(Adverb(Arity::Binary, Arity::Binary)),
-            "fold" => Some(Adverb(Arity::Unary, Arity::Unary)),
-            "flip" => Some(Adverb(Arity::Unary, Arity::Binary)),
+            "." => Some(Adverb(Arity::Binary, Arity::Binary, ANY_OPERANDS)),
+            "fold" => Some(Adverb(Arity::Unary, Arity::Unary, ANY_OPERANDS)),
+            "flip" => Some(Adverb(Arity::Unary, Arity::Binary, ANY_OPERANDS)),
             _ => None,
         }
     }
 
     // TODO: this is kinda duplicated with parse_to_completion
     fn test_body(input: &str) -> String {
-        let tokens = crate::tokenizer::tokenize(input);
+        let tokens = crate::tokenizer::tokenize(input).unwrap();
         let statements = crate::statement_parser::parse_tokens(tokens).unwrap();
         let statements = crate::semicolons::rewrite(statements);
         let statements = crate::op_splitter::rewrite(statements);
@@ -1272,7 +2741,7 @@ foo_2 (n) = (+ foo_1 1)
 
     #[test]
     fn test_recursive_reference() {
-        k9::snapshot!(test_body("foo = foo + 1"), "foo depends on foo");
+        k9::snapshot!(test_body("foo = foo + 1"), "foo failed: cycle foo -> foo");
     }
 
     #[test]
@@ -1301,8 +2770,8 @@ bar = foo + 1
 "
             ),
             "
-foo depends on bar
-bar depends on foo
+foo failed: cycle foo -> bar -> foo
+bar depends on failed foo
 "
         );
 
@@ -1315,9 +2784,9 @@ baz = foo + 1
 "
             ),
             "
-foo depends on bar
-bar depends on baz
-baz depends on foo
+foo failed: cycle foo -> bar -> baz -> foo
+bar depends on failed foo
+baz depends on failed foo
 "
         );
     }
@@ -1435,6 +2904,386 @@ foo =
         );
     }
 
+    #[test]
+    fn test_adverb_definitions() {
+        k9::snapshot!(
+            test_body(
+                "
+twice (f : v1) : v1 = f f
+foo = twice neg
+"
+            ),
+            "
+twice (a1) = (fn (f) (<comp> f f))
+foo (v1) = (twice neg)
+"
+        );
+
+        k9::snapshot!(
+            test_body(
+                "
+under (f : v1) (g : v1) : v1 = g f g
+foo = neg under sign
+bar = 1 + fold neg under sign 2
+"
+            ),
+            "
+under (a2) = (fn (f g) (<comp> g (<comp> f g)))
+foo (v1) = (under neg sign)
+bar (n) = (+ 1 ((under (fold neg) sign) 2))
+"
+        );
+
+        k9::snapshot!(
+            test_body(
+                "
+rank (f : v2) (r : n) : v2 = f
+foo = 1 + rank 0 2
+"
+            ),
+            "
+rank (a2) = (fn (f r) f)
+foo (n) = ((rank + 0) 1 2)
+"
+        );
+    }
+
+    #[test]
+    fn test_adverb_definition_with_block() {
+        k9::snapshot!(
+            test_body(
+                "
+thrice (f : v1) : v1 =
+  g = f f
+  f g
+foo = thrice neg
+"
+            ),
+            "
+thrice (a1) = (fn (f) (let ((g (<comp> f f))) (<comp> f g)))
+foo (v1) = (thrice neg)
+"
+        );
+    }
+
+    #[test]
+    fn test_declared_definitions_schedule_early() {
+        // the use of twice is parsed before the body of twice is complete
+        k9::snapshot!(
+            test_body(
+                "
+foo = twice neg
+twice (f : v1) : v1 = f f
+"
+            ),
+            "
+foo (v1) = (twice neg)
+twice (a1) = (fn (f) (<comp> f f))
+"
+        );
+        k9::snapshot!(
+            test_body(
+                "
+foo = bar 1
+bar : v1 = neg
+"
+            ),
+            "
+foo (n) = (bar 1)
+bar (v1) = neg
+"
+        );
+    }
+
+    #[test]
+    fn test_separate_declarations() {
+        k9::snapshot!(
+            test_body(
+                "
+<+> : v2
+foo = 1 <+> 2
+<+> (x : n) (y : n) : n = x + y
+"
+            ),
+            "
+foo (n) = (<+> 1 2)
+<+> (v2) = (fn (x y) (+ x y))
+"
+        );
+        k9::snapshot!(
+            test_body(
+                "
+twice (f : v1) : v1
+foo = twice neg
+twice (g : v1) : v1 = g g
+"
+            ),
+            "
+foo (v1) = (twice neg)
+twice (a1) = (fn (g) (<comp> g g))
+"
+        );
+        k9::snapshot!(
+            test_body("b : n\n(a b) = (1 2)\nc = b"),
+            "
+(a b) (n) = (<tuple> 1 2)
+a (n) = (<element 0/2> (a b))
+b (n) = (<element 1/2> (a b))
+c (n) = b
+"
+        );
+    }
+
+    #[test]
+    fn test_destructuring() {
+        k9::snapshot!(
+            test_body("(a b) = (1 2)\nc = a + b"),
+            "
+(a b) (n) = (<tuple> 1 2)
+a (n) = (<element 0/2> (a b))
+b (n) = (<element 1/2> (a b))
+c (n) = (+ a b)
+"
+        );
+        // the elements can be used before the tuple is parsed
+        k9::snapshot!(
+            test_body("c = a + b\n(a b) = f 1\nf (x : n) : n = (x x)"),
+            "
+c (n) = (+ a b)
+(a b) (n) = (f 1)
+a (n) = (<element 0/2> (a b))
+b (n) = (<element 1/2> (a b))
+f (v1) = (fn (x) (<tuple> x x))
+"
+        );
+    }
+
+    #[test]
+    fn test_scripts() {
+        fn test(input: &str) -> String {
+            let script = crate::parse_script(input).unwrap();
+            script
+                .results
+                .iter()
+                .map(|id| format!("{} = {}", id, script.assignments[id]))
+                .collect::<Vec<_>>()
+                .join("\n")
+        }
+        k9::snapshot!(
+            test("x = 1\nx + 1\ny = 2\nneg y\nx"),
+            "
+_ = (+ x 1)
+_ = (neg y)
+_ = x
+"
+        );
+        // only the top level has more than one result
+        k9::snapshot!(
+            test("f =\n  1\n  2\nf\nf"),
+            "
+_ = f
+_ = f
+"
+        );
+    }
+
+    #[test]
+    fn test_recursive_definitions() {
+        k9::snapshot!(
+            test_body(
+                "
+fact (x : n) : n =
+  if x
+    x * fact x + neg 1
+  else
+    1
+"
+            ),
+            "fact (v1) = (fn (x) (let () (if x (* x (fact (+ x (neg 1)))) 1)))"
+        );
+        k9::snapshot!(
+            test_body(
+                "
+even (x : n) : n =
+  if x
+    odd x + neg 1
+  else
+    1
+odd (x : n) : n =
+  if x
+    even x + neg 1
+  else
+    0
+"
+            ),
+            "
+even (v1) = (fn (x) (let () (if x (odd (+ x (neg 1))) 1)))
+odd (v1) = (fn (x_1) (let () (if x_1 (even (+ x_1 (neg 1))) 0)))
+"
+        );
+        k9::snapshot!(test_body("x : n = x + 1"), "x failed: cycle x -> x");
+        k9::snapshot!(test_body("f : v1 = f"), "f (v1) = f");
+    }
+
+    #[test]
+    fn test_verb_definitions() {
+        k9::snapshot!(
+            test_body(
+                "
+double (x : n) : n = x + x
+plus (x : n) (y : n) : n = x + y
+foo = 1 plus double 2
+"
+            ),
+            "
+double (v1) = (fn (x) (+ x x))
+plus (v2) = (fn (x_1 y) (+ x_1 y))
+foo (n) = (plus 1 (double 2))
+"
+        );
+    }
+
+    #[test]
+    fn test_parameters_shadow_outer_names() {
+        k9::snapshot!(
+            test_body(
+                "
+f = 1
+twice (f : v1) : v1 = f f
+g = f
+"
+            ),
+            "
+f (n) = 1
+twice (a1) = (fn (f_1) (<comp> f_1 f_1))
+g (n) = f
+"
+        );
+    }
+
+    #[test]
+    fn test_signature_errors() {
+        k9::snapshot!(
+            test_body("twice (f : v1) : v2 = f f"),
+            "twice failed: PartOfSpeechMismatch(Verb(Binary), Verb(Unary))"
+        );
+        k9::snapshot!(
+            test_body("foo : n = neg"),
+            "foo failed: PartOfSpeechMismatch(Noun, Verb(Unary))"
+        );
+        k9::snapshot!(
+            test_body("apply (f : v1) : n = f 1"),
+            "apply failed: InvalidSignature"
+        );
+        k9::snapshot!(
+            test_body("lift (x : n) : v1 = + x"),
+            "lift failed: InvalidSignature"
+        );
+        k9::snapshot!(
+            test_body(
+                "
+twice (f : v1) : v1 = f f
+foo = twice +
+"
+            ),
+            "
+twice (a1) = (fn (f) (<comp> f f))
+foo failed: OperandMismatch(\"twice\", 0, Verb(Unary), Verb(Binary))
+"
+        );
+    }
+
+    #[test]
+    fn test_operand_errors() {
+        let apply = "apply (f : v1) (x : n) : v1 = f\n";
+        k9::snapshot!(
+            test_body(&format!("{}g = neg apply 3", apply)),
+            "
+apply (a2) = (fn (f x) f)
+g (v1) = (apply neg 3)
+"
+        );
+        k9::snapshot!(
+            parse_error(&format!("{}(2 apply 3) 4", apply)),
+            "_: the first operand of apply is declared as v1 but given n"
+        );
+        k9::snapshot!(
+            parse_error(&format!("{}(neg apply neg) 4", apply)),
+            "_: the second operand of apply is declared as n but given v1"
+        );
+        k9::snapshot!(
+            parse_error(&format!("{}(+ apply 1) 4", apply)),
+            "_: the first operand of apply is declared as v1 but given v2"
+        );
+        k9::snapshot!(
+            parse_error("twice (f : v1) : v1 = f f\n(twice -) 1"),
+            "_: the first operand of twice is declared as v1 but given v2"
+        );
+    }
+
+    #[test]
+    fn test_conditionals() {
+        k9::snapshot!(
+            test_body(
+                "
+x = 1
+y =
+  if x
+    neg x
+  else
+    z = x + 1
+    z
+"
+            ),
+            "
+x (n) = 1
+y (n) = (let () (if x (neg x) (let ((z (+ x 1))) z)))
+"
+        );
+
+        k9::snapshot!(
+            test_body(
+                "
+f =
+  if 1
+    neg
+  else if 0
+    sign
+  else
+    neg sign
+"
+            ),
+            "f (v1) = (let () (if 1 neg (if 0 sign (<comp> neg sign))))"
+        );
+    }
+
+    #[test]
+    fn test_conditional_errors() {
+        k9::snapshot!(
+            test_body(
+                "
+f =
+  if neg
+    1
+  else
+    2
+"
+            ),
+            "f failed: _: declared as n but defined as v1"
+        );
+        k9::snapshot!(
+            test_body(
+                "
+if 1
+  1
+else
+  neg
+"
+            ),
+            "_ failed: PartOfSpeechMismatch(Noun, Verb(Unary))"
+        );
+    }
+
     #[test]
     fn subassignment_failure() {
         k9::snapshot!(
@@ -1445,7 +3294,309 @@ foo =
   10
 "
             ),
-            "foo failed: SubAssignmentFailed"
+            "foo failed: x (line 3): array literals can only contain nouns"
+        );
+    }
+
+    fn parse_error(input: &str) -> String {
+        crate::parse(input).unwrap_err().to_string()
+    }
+
+    #[test]
+    fn test_declaration_errors() {
+        k9::snapshot!(
+            parse_error("<+> : v1\n<+> (x : n) (y : n) : n = x + y\n1 <+> 2"),
+            "<+> (line 2): declared as v1 but defined as v2"
+        );
+        k9::snapshot!(
+            parse_error("x : v1\nx = 1\nx"),
+            "x (line 2): declared as v1 but defined as n"
+        );
+        k9::snapshot!(
+            parse_error("<+> : v2\n1 <+> 2"),
+            "<+> (line 1): declared but never defined"
+        );
+        // only the last declaration before the definition counts, and the
+        // others are errors
+        k9::snapshot!(
+            parse_error("<+> : v2\n<+> : v2\n<+> = +\n1 <+> 2"),
+            "<+> (line 1): declared again before being defined"
+        );
+        k9::snapshot!(
+            parse_error("x : n\nx : n\nx = 1\nx"),
+            "x (line 1): declared again before being defined"
+        );
+        // but a name can be declared again once it's defined
+        k9::snapshot!(
+            test_body("x : n\nx = 1\nx : n\nx = x + 1"),
+            "
+x (n) = 1
+x_1 (n) = (+ x 1)
+"
+        );
+    }
+
+    #[test]
+    fn test_destructuring_errors() {
+        k9::snapshot!(
+            parse_error("(a b) = neg\na"),
+            "(a b): declared as n but defined as v1"
+        );
+        k9::snapshot!(
+            parse_error("(a b) = (zed 1)\na"),
+            "(a b): zed is not defined"
+        );
+        k9::snapshot!(
+            parse_error("(a b) = (b 1)\na"),
+            "cyclic definitions: (a b) -> b (line 1) -> (a b)"
+        );
+        k9::snapshot!(
+            parse_error("b : v1\n(a b) = (1 2)\nb"),
+            "b (line 2): declared as v1 but defined as n"
+        );
+    }
+
+    #[test]
+    fn test_cycle_errors() {
+        k9::snapshot!(
+            parse_error(
+                "
+a = b + 1
+b = c + 1
+c = a + 1
+a
+"
+            ),
+            "cyclic definitions: a (line 2) -> b (line 3) -> c (line 4) -> a"
+        );
+        k9::snapshot!(
+            parse_error(
+                "
+x = 1
+b = c + x
+c =
+  y = b
+  y + 1
+c
+"
+            ),
+            "cyclic definitions: b (line 3) -> c (line 4) -> b"
+        );
+    }
+
+    #[test]
+    fn test_all_errors_are_reported() {
+        let input = "
+a = [+]
+b = a + 1
+c : v1 = 1
+d =
+  x = [neg]
+  y = x
+  z = e + 1
+  z
+e = z + 1
+z = e
+d
+";
+        k9::snapshot!(
+            parse_error(input),
+            "
+a (line 2): array literals can only contain nouns
+c (line 4): declared as v1 but defined as n
+d (line 5): x (line 6): array literals can only contain nouns
+cyclic definitions: e (line 10) -> z (line 11) -> e
+"
+        );
+        let error = crate::parse(input).unwrap_err();
+        let failures = error
+            .failures()
+            .iter()
+            .map(|failure| {
+                let path = failure
+                    .bindings
+                    .iter()
+                    .map(|binding| binding.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(".");
+                let kind = if failure.is_root_cause() {
+                    "root cause"
+                } else {
+                    "cascaded"
+                };
+                format!("{} {}", path, kind)
+            })
+            .collect::<Vec<_>>();
+        k9::snapshot!(
+            failures.join("\n"),
+            "
+a root cause
+b cascaded
+c root cause
+d.x root cause
+d.y cascaded
+d.z cascaded
+d._ cascaded
+e root cause
+z cascaded
+_ cascaded
+"
+        );
+    }
+
+    #[test]
+    fn test_unbound_names() {
+        k9::snapshot!(
+            parse_error("nge 5"),
+            "_: nge is not defined; did you mean neg?"
+        );
+        k9::snapshot!(
+            parse_error(
+                "
+total = 1
+x =
+  y = totl + 1
+  y
+a = x + undefined
+a
+"
+            ),
+            "
+x (line 3): y (line 4): totl is not defined; did you mean total?
+a (line 6): undefined is not defined
+"
+        );
+        k9::snapshot!(
+            parse_error(
+                "
+f (count : n) (x : n) : n = cont + x
+f 1
+"
+            ),
+            "f (line 2): cont is not defined; did you mean count?"
+        );
+        k9::snapshot!(
+            parse_error("mid = 1\nmix = 2\nmim + 1"),
+            "_: mim is not defined; did you mean mid, min, or mix?"
+        );
+        k9::snapshot!(
+            parse_error("mix = 2\nmim + 1"),
+            "_: mim is not defined; did you mean min or mix?"
+        );
+        // one letter isn't enough to go on
+        k9::snapshot!(parse_error("x = 1\ny + x"), "_: y is not defined");
+        k9::snapshot!(parse_error("1 ? 2"), "_: unknown operator ?");
+        k9::snapshot!(
+            parse_error("| = +\n&& = *\n|& = -\n1 |&& 2"),
+            "_: unknown operator |&&; did you mean | &&?"
+        );
+
+        let error = crate::parse("x =\n  y = 1\n  z = w\n  z\nx").unwrap_err();
+        let locations = error
+            .failures()
+            .iter()
+            .filter_map(|failure| match failure.error {
+                ParseError::UnboundName(name, location, _) => {
+                    Some(format!("{} {} offset {}", name, location, location.offset))
+                }
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        k9::snapshot!(locations.join(", "), "w line 3 offset 18");
+    }
+
+    fn show_symbols(input: &str) -> String {
+        let (_, symbols) = crate::parse_with_symbols(input);
+        let show = |symbol: &Symbol| {
+            let pos = match symbol.pos {
+                Some(pos) => pos.to_string(),
+                None => "?".to_string(),
+            };
+            format!(
+                "{}#{} {}:{} {}",
+                symbol.name, symbol.id, symbol.location.line, symbol.location.offset, pos
+            )
+        };
+        let definitions = symbols.definitions.iter().map(show).collect::<Vec<_>>();
+        let references = symbols.references.iter().map(show).collect::<Vec<_>>();
+        format!(
+            "\ndefinitions:\n{}\nreferences:\n{}\n",
+            definitions.join("\n"),
+            references.join("\n")
+        )
+    }
+
+    #[test]
+    fn test_symbols() {
+        let first_id = crate::prelude::PRELUDE.len();
+        let input = "x = 1\nf (y : n) : n = y + x\nf x";
+        let (_, symbols) = crate::parse_with_symbols(input);
+        let definitions = symbols
+            .definitions
+            .iter()
+            .map(|symbol| (symbol.name.as_str(), symbol.id - first_id as Identifier))
+            .collect::<Vec<_>>();
+        assert_eq!(definitions, vec![("x", 0), ("f", 1), ("y", 2)]);
+        let references = symbols
+            .references
+            .iter()
+            .map(|symbol| &input[symbol.location.offset..][..symbol.name.len()])
+            .collect::<Vec<_>>();
+        assert_eq!(references, vec!["y", "+", "x", "f", "x"]);
+        // failures still have symbols, just without parts of speech
+        k9::snapshot!(
+            show_symbols("x = [+]\ny = x\nz = w\ny"),
+            "
+definitions:
+x#26 1:0 ?
+y#27 2:8 ?
+z#28 3:14 ?
+references:
++#0 1:5 v2
+x#26 2:12 ?
+y#27 4:20 ?
+"
+        );
+    }
+
+    #[test]
+    fn test_bad_reference_errors() {
+        k9::snapshot!(
+            parse_error(
+                "
+foo = [+]
+bar = foo + 1
+baz = bar + 1
+baz
+"
+            ),
+            "foo (line 2): array literals can only contain nouns"
+        );
+        k9::snapshot!(
+            parse_error(
+                "
+f =
+  x = [+]
+  10
+g = f + 1
+g
+"
+            ),
+            "f (line 2): x (line 3): array literals can only contain nouns"
+        );
+        k9::snapshot!(
+            test_body(
+                "
+foo = [+]
+bar = foo + 1
+baz = bar + 1
+"
+            ),
+            "
+foo failed: ArrayLiteralNotNoun
+bar depends on failed foo
+baz depends on failed foo
+"
         );
     }
 }
@@ -0,0 +1,51 @@
+use crate::expression::{Identifier, RichIdentifier};
+use crate::pos_parser::{Arity::*, PartOfSpeech, PartOfSpeech::*, ANY_OPERANDS};
+
+// Every program is parsed as if it were nested inside a block that defines
+// these names. Prelude names are given the first identifiers, in the order that
+// they appear here, so that an evaluator can recognize references to them.
+pub const PRELUDE: &[(&str, PartOfSpeech)] = &[
+    ("+", Verb(Binary)),
+    ("-", Verb(Binary)),
+    ("*", Verb(Binary)),
+    ("/", Verb(Binary)),
+    ("%", Verb(Binary)),
+    ("max", Verb(Binary)),
+    ("min", Verb(Binary)),
+    ("==", Verb(Binary)),
+    ("!=", Verb(Binary)),
+    ("<", Verb(Binary)),
+    (">", Verb(Binary)),
+    ("<=", Verb(Binary)),
+    (">=", Verb(Binary)),
+    ("neg", Verb(Unary)),
+    ("sign", Verb(Unary)),
+    ("abs", Verb(Unary)),
+    ("iota", Verb(Unary)),
+    ("first", Verb(Unary)),
+    ("second", Verb(Unary)),
+    ("length", Verb(Unary)),
+    ("fold", Adverb(Unary, Unary, ANY_OPERANDS)),
+    ("flip", Adverb(Unary, Binary, ANY_OPERANDS)),
+    ("~", Adverb(Unary, Unary, ANY_OPERANDS)),
+    ("fix", Adverb(Unary, Unary, ANY_OPERANDS)),
+    (".", Adverb(Binary, Binary, ANY_OPERANDS)),
+    ("power", Adverb(Binary, Unary, ANY_OPERANDS)),
+];
+
+pub fn name_of(id: Identifier) -> Option<&'static str> {
+    PRELUDE.get(id as usize).map(|(name, _)| *name)
+}
+
+pub(super) fn lookup(name: &str) -> Option<(RichIdentifier, PartOfSpeech)> {
+    PRELUDE
+        .iter()
+        .enumerate()
+        .find(|(_, (prelude_name, _))| *prelude_name == name)
+        .map(|(id, (name, pos))| {
+            (
+                RichIdentifier::new(id as Identifier, name.to_string()),
+                *pos,
+            )
+        })
+}
@@ -0,0 +1,393 @@
+use crate::expression::{Builtin, Expression, Identifier, RichIdentifier};
+use crate::pos_parser::PartOfSpeech;
+
+// Prints an expression as petal source that parses back to the exact same
+// expression -- identifiers and all. Implicit builtins turn back into
+// juxtaposition and coefficients, and parens are only written where the
+// expression has a Parens node, so this relies on the expression having come
+// out of the parser in the first place.
+//
+// Identifiers are allocated in the order that statements appear, so the
+// statements of every block are printed in the order of their identifiers.
+// The result of a block doesn't keep its identifier, but it leaves a gap
+// where it used to be.
+pub fn print(expr: &Expression) -> String {
+    let mut output = String::new();
+    let first_id = crate::prelude::PRELUDE.len() as Identifier;
+    block(&mut output, expr, first_id, 0);
+    output
+}
+
+const INDENT: &str = "  ";
+
+fn line(output: &mut String, depth: usize, text: &str) {
+    output.push_str(&INDENT.repeat(depth));
+    output.push_str(text);
+    output.push('\n');
+}
+
+// The number of identifiers that parsing a block allocates. Conditional
+// branches that consist of a single expression aren't wrapped in a compound,
+// but they're still blocks.
+fn block_size(expr: &Expression) -> Identifier {
+    match expr {
+        Expression::Compound(assignments, result) => {
+            let assignments: Identifier = assignments
+                .iter()
+                .map(|(id, value)| statement_size(id, value))
+                .sum();
+            assignments + result_size(result)
+        }
+        expr => result_size(expr),
+    }
+}
+
+fn result_size(expr: &Expression) -> Identifier {
+    match expr {
+        Expression::Conditional(_, consequent, alternative) => {
+            1 + block_size(consequent) + block_size(alternative)
+        }
+        _ => 1,
+    }
+}
+
+fn statement_size(id: &RichIdentifier, value: &Expression) -> Identifier {
+    if id.name == "_" {
+        return result_size(value);
+    }
+    let (params, body) = split_lambda(value);
+    1 + params.len() as Identifier + nested_size(body)
+}
+
+// Compound assignments parse their block before learning their own name.
+fn nested_size(body: &Expression) -> Identifier {
+    match body {
+        Expression::Compound(_, _) => block_size(body),
+        _ => 0,
+    }
+}
+
+fn split_lambda(value: &Expression) -> (&[(RichIdentifier, PartOfSpeech)], &Expression) {
+    match value {
+        Expression::Lambda(params, body, _) => (params, body),
+        value => (&[], value),
+    }
+}
+
+fn block(output: &mut String, expr: &Expression, first_id: Identifier, depth: usize) {
+    let (assignments, result) = match expr {
+        Expression::Compound(assignments, result) => (assignments, result.as_ref()),
+        expr => return result_statement(output, expr, first_id, depth),
+    };
+    let mut assignments = assignments.iter().collect::<Vec<_>>();
+    assignments.sort_by_key(|(id, _)| id.id);
+
+    let mut next_id = first_id;
+    let mut result = Some(result);
+    for (id, value) in assignments {
+        let start = if id.name == "_" {
+            id.id
+        } else {
+            id.id - nested_size(split_lambda(value).1)
+        };
+        if start > next_id {
+            if let Some(result) = result.take() {
+                result_statement(output, result, next_id, depth);
+            }
+        }
+        if id.name == "_" {
+            result_statement(output, value, start, depth);
+        } else if let Expression::Element(_, _, _) = value {
+            // the tuple that this came from already printed the whole pattern
+        } else {
+            assignment(output, id, value, start, depth);
+        }
+        next_id = start + statement_size(id, value);
+    }
+    if let Some(result) = result {
+        result_statement(output, result, next_id, depth);
+    }
+}
+
+fn result_statement(output: &mut String, expr: &Expression, id: Identifier, depth: usize) {
+    match expr {
+        Expression::Conditional(condition, consequent, alternative) => {
+            line(output, depth, &format!("if {}", terms(condition)));
+            block(output, consequent, id + 1, depth + 1);
+            line(output, depth, "else");
+            let alternative_id = id + 1 + block_size(consequent);
+            block(output, alternative, alternative_id, depth + 1);
+        }
+        expr => line(output, depth, &terms(expr)),
+    }
+}
+
+fn assignment(
+    output: &mut String,
+    id: &RichIdentifier,
+    value: &Expression,
+    start: Identifier,
+    depth: usize,
+) {
+    let head = format!("{}{}", id, signature(value));
+    match split_lambda(value).1 {
+        body @ Expression::Compound(_, _) => {
+            line(output, depth, &format!("{} =", head));
+            block(output, body, start, depth + 1);
+        }
+        body => line(output, depth, &format!("{} = {}", head, terms(body))),
+    }
+}
+
+// Definitions without parameters don't keep their signature around, but they
+// don't need it: the body has the same part of speech either way.
+fn signature(value: &Expression) -> String {
+    let (params, pos) = match value {
+        Expression::Lambda(params, _, pos) => (params, pos),
+        _ => return String::new(),
+    };
+    let result = match pos {
+        PartOfSpeech::Verb(_) => PartOfSpeech::Noun,
+        PartOfSpeech::Adverb(_, arity, _) => PartOfSpeech::Verb(*arity),
+        PartOfSpeech::Noun => panic!("noun with parameters"),
+    };
+    let mut signature = String::new();
+    for (param, pos) in params {
+        signature += &format!(" ({} : {})", param, pos);
+    }
+    signature + &format!(" : {}", result)
+}
+
+// Coefficients have to be written right up against the thing that they scale.
+enum Word {
+    Coefficient(String),
+    Term(String),
+}
+
+impl Word {
+    fn text(&self) -> &str {
+        match self {
+            Word::Coefficient(text) | Word::Term(text) => text,
+        }
+    }
+}
+
+fn terms(expr: &Expression) -> String {
+    let mut result = vec![];
+    words(expr, &mut result);
+    join(&result)
+}
+
+fn join(words: &[Word]) -> String {
+    words.iter().map(Word::text).collect::<Vec<_>>().join(" ")
+}
+
+// Builtins whose operands were juxtaposed in the opposite order.
+fn reversed(builtin: Builtin) -> bool {
+    match builtin {
+        Builtin::PartialApplicationLeft | Builtin::ComposeLeft => true,
+        Builtin::Compose | Builtin::PartialApplicationRight | Builtin::ComposeRight => false,
+        Builtin::Scale => panic!("binary scale"),
+    }
+}
+
+fn words(expr: &Expression, result: &mut Vec<Word>) {
+    use Expression::*;
+    match expr {
+        Identifier(id) => result.push(Word::Term(id.name.clone())),
+        NumericLiteral(num) => result.push(Word::Term(num.clone())),
+        // the coefficient grouper wraps the coefficient and the term that it
+        // scales in parens
+        Parens(inner) => {
+            let mut inner_words = vec![];
+            words(inner, &mut inner_words);
+            let word = match inner_words.as_slice() {
+                [Word::Coefficient(coefficient), Word::Term(term)] => {
+                    format!("{}{}", coefficient, term)
+                }
+                inner_words => format!("({})", join(inner_words)),
+            };
+            result.push(Word::Term(word));
+        }
+        Brackets(exprs) => {
+            let mut inner_words = vec![];
+            for expr in exprs {
+                words(expr, &mut inner_words);
+            }
+            result.push(Word::Term(format!("[{}]", join(&inner_words))));
+        }
+        Tuple(exprs) => {
+            for expr in exprs {
+                words(expr, result);
+            }
+        }
+        UnaryApplication(f, x) => match (f.as_ref(), x.as_ref()) {
+            (Implicit(Builtin::Scale), NumericLiteral(num)) => {
+                let coefficient = if num == "-1" { "-" } else { num };
+                result.push(Word::Coefficient(coefficient.to_string()));
+            }
+            _ => {
+                words(f, result);
+                words(x, result);
+            }
+        },
+        BinaryApplication(f, lhs, rhs) => match f.as_ref() {
+            Implicit(builtin) if reversed(*builtin) => {
+                words(rhs, result);
+                words(lhs, result);
+            }
+            Implicit(_) => {
+                words(lhs, result);
+                words(rhs, result);
+            }
+            f => {
+                words(lhs, result);
+                words(f, result);
+                words(rhs, result);
+            }
+        },
+        Implicit(_)
+        | Compound(_, _)
+        | Lambda(_, _, _)
+        | Conditional(_, _, _)
+        | Element(_, _, _) => {
+            panic!("{} can't be written as a term", expr)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn round_trip(input: &str) -> String {
+        let (expr, _) = crate::parse(input).unwrap();
+        let output = print(&expr);
+        let (reparsed, _) = crate::parse(&output).unwrap();
+        assert_eq!(reparsed, expr, "{:?} => {:?}", input, output);
+        output.trim_end().to_string()
+    }
+
+    #[test]
+    fn test_implicit_builtins() {
+        k9::snapshot!(round_trip("(neg sign) 1"), "(neg sign) 1");
+        k9::snapshot!(round_trip("(* 2) 3"), "(* 2) 3");
+        k9::snapshot!(round_trip("(2 *) 3"), "(2 *) 3");
+        k9::snapshot!(round_trip("1 (+ neg) 2"), "1 (+ neg) 2");
+        k9::snapshot!(round_trip("1 (neg +) 2"), "1 (neg +) 2");
+        k9::snapshot!(round_trip("fold+ 1 2 3"), "fold + 1 2 3");
+    }
+
+    #[test]
+    fn test_coefficients() {
+        k9::snapshot!(
+            round_trip("x = 3\n2x + -x + -1(x) - 15[1 2]"),
+            "
+x = 3
+2x + -x + -(x) - 15[1 2]
+"
+        );
+        k9::snapshot!(round_trip("x = 3\n(2neg) x"), "x = 3\n(2neg) x");
+    }
+
+    #[test]
+    fn test_blocks() {
+        k9::snapshot!(
+            round_trip(
+                "
+double (x : n) : n = x * 2
+y + 1
+y =
+  z = 1
+  z * 2
+twice (f : v1) : v1 =
+  g = f f
+  g
+x =
+  if y > 0
+    double y
+  else
+    z = neg y
+    z
+"
+            ),
+            "
+double (x : n) : n = x * 2
+y + 1
+y =
+  z = 1
+  z * 2
+twice (f : v1) : v1 =
+  g = f f
+  g
+x =
+  if y > 0
+    double y
+  else
+    z = neg y
+    z
+"
+        );
+    }
+
+    #[test]
+    fn test_destructuring() {
+        k9::snapshot!(
+            round_trip("(a b) = (1 2)\na + b\n(c d) = (a b)"),
+            "
+(a b) = (1 2)
+a + b
+(c d) = (a b)
+"
+        );
+    }
+
+    // A tiny deterministic random number generator, so that failures are
+    // reproducible.
+    struct Random(u64);
+
+    impl Random {
+        fn below(&mut self, n: usize) -> usize {
+            self.0 = self.0.wrapping_mul(6364136223846793005).wrapping_add(1);
+            ((self.0 >> 33) % n as u64) as usize
+        }
+
+        fn pick<'a>(&mut self, choices: &[&'a str]) -> &'a str {
+            choices[self.below(choices.len())]
+        }
+    }
+
+    fn soup(random: &mut Random, depth: usize) -> String {
+        let atoms = [
+            "x", "y", "1", "-2", "neg", "sign", "+", "-", "*", "fold", "flip", ".", "power", "2x",
+            "-y", "3neg",
+        ];
+        let length = 1 + random.below(5);
+        let mut terms = vec![];
+        for _ in 0..length {
+            let term = match random.below(if depth == 0 { 1 } else { 6 }) {
+                0..=3 => random.pick(&atoms).to_string(),
+                4 => format!("({})", soup(random, depth - 1)),
+                _ => format!("[{}]", soup(random, depth - 1)),
+            };
+            terms.push(term);
+        }
+        terms.join(" ")
+    }
+
+    // Parses a whole lot of random nonsense, and checks that everything that
+    // parses can be printed and parsed back to the same thing.
+    #[test]
+    fn test_round_trip_property() {
+        let mut random = Random(1);
+        let mut parsed = 0;
+        for _ in 0..5000 {
+            let input = format!("x = 1\ny = [1 2]\n{}", soup(&mut random, 2));
+            if crate::parse(&input).is_ok() {
+                round_trip(&input);
+                parsed += 1;
+            }
+        }
+        assert!(parsed > 500, "only {} programs parsed", parsed);
+    }
+}
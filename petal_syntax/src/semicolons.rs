@@ -1,5 +1,6 @@
+use crate::location::Location;
 use crate::statement::*;
-use crate::terms::{SemiSoupyTerm, SoupyTerm};
+use crate::terms::{Delimiters, SemiSoupyTerm, SoupyTerm};
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
 enum Delimiter {
@@ -7,31 +8,54 @@ enum Delimiter {
     Brackets,
 }
 
-fn wrap(terms: Vec<SoupyTerm>, delimiter: &Delimiter) -> SoupyTerm {
+// The delimiters that we add span the terms inside of them, or the semicolons
+// next to them if they're empty.
+fn wrap(terms: Vec<SoupyTerm>, delimiter: &Delimiter, semicolons: Location) -> SoupyTerm {
+    let delimiters = bounds(&terms).unwrap_or(Delimiters {
+        open: semicolons,
+        close: semicolons,
+    });
     match delimiter {
-        Delimiter::Parens => SoupyTerm::Parens(terms),
-        Delimiter::Brackets => SoupyTerm::Brackets(terms),
+        Delimiter::Parens => SoupyTerm::Parens(terms, delimiters),
+        Delimiter::Brackets => SoupyTerm::Brackets(terms, delimiters),
     }
 }
 
+fn bounds(terms: &[SoupyTerm]) -> Option<Delimiters> {
+    let mut bounds = terms.iter().filter_map(|term| match term {
+        SoupyTerm::Identifier(name)
+        | SoupyTerm::PunctuationSoup(name)
+        | SoupyTerm::NumericLiteral(name) => Some((name.location, name.location)),
+        SoupyTerm::Parens(_, delimiters) | SoupyTerm::Brackets(_, delimiters) => {
+            Some((delimiters.open, delimiters.close))
+        }
+        SoupyTerm::Space => None,
+    });
+    let (open, close) = bounds.next()?;
+    let close = bounds.next_back().map_or(close, |(_, close)| close);
+    Some(Delimiters { open, close })
+}
+
 fn resolve_semicolons(terms: Vec<SemiSoupyTerm>, delimiter: Delimiter) -> Vec<SoupyTerm> {
     let mut index_levels: Vec<usize> = vec![];
     let mut result: Vec<SoupyTerm> = vec![];
+    let mut last_semicolons = None;
     for term in terms {
         match term {
             SemiSoupyTerm::Space => result.push(SoupyTerm::Space),
             SemiSoupyTerm::Identifier(s) => result.push(SoupyTerm::Identifier(s)),
             SemiSoupyTerm::NumericLiteral(s) => result.push(SoupyTerm::NumericLiteral(s)),
             SemiSoupyTerm::PunctuationSoup(s) => result.push(SoupyTerm::PunctuationSoup(s)),
-            SemiSoupyTerm::Parens(terms) => result.push(SoupyTerm::Parens(resolve_semicolons(
-                terms,
-                Delimiter::Parens,
-            ))),
-            SemiSoupyTerm::Brackets(terms) => result.push(SoupyTerm::Brackets(resolve_semicolons(
-                terms,
-                Delimiter::Brackets,
-            ))),
-            SemiSoupyTerm::Semicolons(level) => {
+            SemiSoupyTerm::Parens(terms, delimiters) => result.push(SoupyTerm::Parens(
+                resolve_semicolons(terms, Delimiter::Parens),
+                delimiters,
+            )),
+            SemiSoupyTerm::Brackets(terms, delimiters) => result.push(SoupyTerm::Brackets(
+                resolve_semicolons(terms, Delimiter::Brackets),
+                delimiters,
+            )),
+            SemiSoupyTerm::Semicolons(level, location) => {
+                last_semicolons = Some(location);
                 if index_levels.len() < level {
                     index_levels.resize(level, 0)
                 }
@@ -39,16 +63,17 @@ fn resolve_semicolons(terms: Vec<SemiSoupyTerm>, delimiter: Delimiter) -> Vec<So
 
                 for start_index in index_levels.iter_mut().take(level) {
                     let to_wrap = result.drain(*start_index..).collect();
-                    result.push(wrap(to_wrap, &delimiter));
+                    result.push(wrap(to_wrap, &delimiter, location));
                     *start_index = next_index;
                 }
             }
         }
     }
 
+    // there are only levels to close if there were semicolons
     for start_index in index_levels {
         let to_wrap = result.drain(start_index..).collect();
-        result.push(wrap(to_wrap, &delimiter));
+        result.push(wrap(to_wrap, &delimiter, last_semicolons.unwrap()));
     }
     result
 }
@@ -58,7 +83,7 @@ mod tests {
     use super::*;
 
     fn test(input: &str) -> String {
-        let tokens = crate::tokenizer::tokenize(input);
+        let tokens = crate::tokenizer::tokenize(input).unwrap();
         let terms = crate::statement_parser::parse_expression(tokens).unwrap();
         resolve_semicolons(terms, Delimiter::Parens)
             .iter()
@@ -112,11 +137,21 @@ pub(super) fn rewrite(block: Block<SemiSoupyTerm>) -> Block<SoupyTerm> {
     block
         .into_iter()
         .map(|statement: Statement<SemiSoupyTerm>| match statement {
-            SimpleAssignment(id, terms) => {
-                SimpleAssignment(id, resolve_semicolons(terms, Delimiter::Parens))
+            SimpleAssignment(id, sig, terms) => {
+                SimpleAssignment(id, sig, resolve_semicolons(terms, Delimiter::Parens))
             }
-            CompoundAssignment(id, block) => CompoundAssignment(id, rewrite(block)),
+            CompoundAssignment(id, sig, block) => CompoundAssignment(id, sig, rewrite(block)),
             Expression(terms) => Expression(resolve_semicolons(terms, Delimiter::Parens)),
+            Declaration(id, sig) => Declaration(id, sig),
+            Destructuring(names, terms) => {
+                Destructuring(names, resolve_semicolons(terms, Delimiter::Parens))
+            }
+            Conditional(terms, consequent, otherwise, alternative) => Conditional(
+                resolve_semicolons(terms, Delimiter::Parens),
+                rewrite(consequent),
+                otherwise,
+                rewrite(alternative),
+            ),
         })
         .collect()
 }
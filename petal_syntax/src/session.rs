@@ -0,0 +1,462 @@
+use crate::expression::Expression;
+use crate::located_token::LocatedToken;
+use crate::location::{Location, Origin};
+use crate::pos_parser::{ParseCache, ParseError, PartOfSpeech, Symbols};
+use crate::statement::Block;
+use crate::terms::{SoupyTerm, Term};
+use std::ops::Range;
+
+// A document that's reparsed after every edit, for editor integrations.
+//
+// The document is split into chunks of lines that each begin with a line that
+// isn't indented, inside of parens, or an `else` -- usually one top-level
+// statement and its body. An edit only re-tokenizes and re-parses the statements of the chunks
+// that it touches, and only the top-level statements whose parse depends on
+// something that changed are parsed again.
+#[derive(Default)]
+pub struct Session {
+    chunks: Vec<Chunk>,
+    // every name defined at the top level, as of the last parse. Any chunk can
+    // use an operator that any other chunk defines, so when this changes,
+    // every chunk has to split its operators again.
+    names: Vec<String>,
+    cache: ParseCache,
+    stats: Stats,
+}
+
+// What the session had to redo after the last edit and parse.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Stats {
+    pub retokenized_lines: usize,
+    pub reparsed_statements: usize,
+}
+
+// Locations are relative to the start of the chunk. Statements aren't parsed
+// until the session is, because a chunk might turn out to be the start of a
+// statement that continues into the next one.
+struct Chunk {
+    text: String,
+    tokens: Result<Vec<LocatedToken>, ParseError>,
+    // before splitting operators
+    soupy: Option<Result<Block<SoupyTerm>, ParseError>>,
+    statements: Option<Block<Term>>,
+}
+
+impl Chunk {
+    fn new(text: String) -> Self {
+        let tokens = crate::tokenizer::tokenize(&text);
+        Chunk {
+            text,
+            tokens,
+            soupy: None,
+            statements: None,
+        }
+    }
+
+    fn soupy(&mut self) -> &Result<Block<SoupyTerm>, ParseError> {
+        let tokens = &self.tokens;
+        self.soupy
+            .get_or_insert_with(|| tokens.clone().and_then(crate::soupy_statements))
+    }
+
+    fn line_count(&self) -> usize {
+        self.text.lines().count()
+    }
+
+    // whether the next line continues the last line of this chunk
+    fn is_open(&self) -> bool {
+        matches!(&self.tokens, Ok(tokens) if crate::tokenizer::open_delimiters(0, tokens) > 0)
+    }
+}
+
+fn chunks(text: &str) -> Vec<Chunk> {
+    let mut chunks = vec![];
+    let mut current = String::new();
+    for line in text.split_inclusive('\n') {
+        // comments don't have to be indented to be part of a block
+        let starts_statement = line.starts_with(|c: char| !c.is_whitespace() && c != '#')
+            && !continues_statement(line);
+        if starts_statement && !current.is_empty() {
+            let chunk = Chunk::new(std::mem::take(&mut current));
+            if chunk.is_open() {
+                current = chunk.text;
+            } else {
+                chunks.push(chunk);
+            }
+        }
+        current.push_str(line);
+    }
+    if !current.is_empty() {
+        chunks.push(Chunk::new(current));
+    }
+    chunks
+}
+
+// `else` isn't indented, but it's part of the conditional before it.
+fn continues_statement(text: &str) -> bool {
+    match text.strip_prefix("else") {
+        Some(rest) => !rest.starts_with(crate::tokenizer::is_identifier_character),
+        None => false,
+    }
+}
+
+impl Session {
+    pub fn new(text: &str) -> Self {
+        let mut session = Session::default();
+        session.edit(0..0, text);
+        session
+    }
+
+    pub fn text(&self) -> String {
+        self.chunks
+            .iter()
+            .map(|chunk| chunk.text.as_str())
+            .collect()
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.stats
+    }
+
+    // Replaces the given byte range of the document.
+    pub fn edit(&mut self, range: Range<usize>, replacement: &str) {
+        let mut spans = vec![];
+        let mut offset = 0;
+        for chunk in &self.chunks {
+            spans.push(offset..offset + chunk.text.len());
+            offset += chunk.text.len();
+        }
+        assert!(range.start <= range.end && range.end <= offset);
+
+        // An edit that touches the boundary between two chunks could change
+        // either of them: it might indent the first line of the second chunk,
+        // or remove the newline at the end of the first.
+        let mut first = spans
+            .iter()
+            .position(|span| span.end >= range.start)
+            .unwrap_or(spans.len());
+        let mut end = spans
+            .iter()
+            .rposition(|span| span.start <= range.end)
+            .map_or(first, |last| last + 1)
+            .max(first);
+        let region_start = spans.get(first).map_or(offset, |span| span.start);
+
+        let mut text = self.chunks[first..end]
+            .iter()
+            .map(|chunk| chunk.text.as_str())
+            .collect::<String>();
+        text.replace_range(
+            (range.start - region_start)..(range.end - region_start),
+            replacement,
+        );
+        // and an edit can turn the first line of a chunk into one that
+        // continues the chunk before it
+        while first > 0 && continues_statement(&text) {
+            first -= 1;
+            text.insert_str(0, &self.chunks[first].text);
+        }
+        // opening a paren can turn the chunks after the edit into
+        // continuation lines
+        let mut new_chunks = chunks(&text);
+        while end < self.chunks.len() && matches!(new_chunks.last(), Some(chunk) if chunk.is_open())
+        {
+            text.push_str(&self.chunks[end].text);
+            end += 1;
+            new_chunks = chunks(&text);
+        }
+        self.stats.retokenized_lines = new_chunks.iter().map(Chunk::line_count).sum();
+        self.chunks.splice(first..end, new_chunks);
+    }
+
+    pub fn parse(&mut self) -> Result<(Expression, PartOfSpeech), ParseError> {
+        self.parse_with_symbols().0
+    }
+
+    pub fn parse_with_symbols(
+        &mut self,
+    ) -> (Result<(Expression, PartOfSpeech), ParseError>, Symbols) {
+        let mut names = vec![];
+        // chunks are tokenized separately, but they all have to be indented
+        // the same way
+        let mut indentation = None;
+        let mut origin = Origin::default();
+        for chunk in &mut self.chunks {
+            if let Ok(tokens) = &chunk.tokens {
+                match (
+                    indentation,
+                    crate::tokenizer::first_indentation(&chunk.text, tokens),
+                ) {
+                    (None, Some((c, _))) => indentation = Some(c),
+                    (Some(expected), Some((c, location))) if c != expected => {
+                        let error = ParseError::MixedIndentation(location);
+                        return (Err(moved(error, origin)), Symbols::default());
+                    }
+                    _ => (),
+                }
+            }
+            match chunk.soupy() {
+                Ok(statements) => names.extend(
+                    crate::op_splitter::defined_names(statements)
+                        .iter()
+                        .map(|name| name.to_string()),
+                ),
+                Err(error) => return (Err(moved(error.clone(), origin)), Symbols::default()),
+            }
+            origin.offset += chunk.text.len();
+            origin.lines += chunk.line_count() as u32;
+        }
+        names.sort();
+        names.dedup();
+        if names != self.names {
+            for chunk in &mut self.chunks {
+                chunk.statements = None;
+            }
+            self.names = names;
+        }
+
+        let mut statements = vec![];
+        let mut origin = Origin::default();
+        for chunk in &mut self.chunks {
+            if chunk.statements.is_none() {
+                let soupy = chunk.soupy().clone().unwrap();
+                chunk.statements = Some(crate::split_statements(soupy, &self.names));
+            }
+            let chunk_statements = chunk.statements.as_ref().unwrap();
+            statements.extend(
+                chunk_statements
+                    .iter()
+                    .map(|statement| (statement.clone(), origin)),
+            );
+            origin.offset += chunk.text.len();
+            origin.lines += chunk.line_count() as u32;
+        }
+        let (result, symbols) = crate::pos_parser::parse_incrementally(statements, &mut self.cache);
+        self.stats.reparsed_statements = self.cache.reparsed;
+        (result, symbols)
+    }
+}
+
+// Errors from a single chunk are relative to the start of the chunk.
+fn moved(error: ParseError, origin: Origin) -> ParseError {
+    let move_location = |location: Location| location.moved(Origin::default(), origin);
+    match error {
+        ParseError::InvalidSyntax(location) => ParseError::InvalidSyntax(move_location(location)),
+        ParseError::MixedIndentation(location) => {
+            ParseError::MixedIndentation(move_location(location))
+        }
+        ParseError::BoundTwice(name, location) => {
+            ParseError::BoundTwice(name, move_location(location))
+        }
+        error => error,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn show(result: Result<(Expression, PartOfSpeech), ParseError>) -> String {
+        match result {
+            Ok((expr, pos)) => format!("{} : {}", expr, pos),
+            Err(error) => error.to_string(),
+        }
+    }
+
+    fn replace(session: &mut Session, old: &str, new: &str) {
+        let start = session.text().find(old).unwrap();
+        session.edit(start..start + old.len(), new);
+    }
+
+    // every parse in a session should match a parse from scratch, down to the
+    // locations of its symbols
+    fn check(session: &mut Session) -> String {
+        let (result, symbols) = session.parse_with_symbols();
+        let (expected_result, expected_symbols) = crate::parse_with_symbols(&session.text());
+        let incremental = show(result);
+        assert_eq!(incremental, show(expected_result));
+        assert_eq!(format!("{:?}", symbols), format!("{:?}", expected_symbols));
+        incremental
+    }
+
+    const PROGRAM: &str = "
+a = 1
+b = a + 1
+f (x : n) : n =
+  y = x * b
+  y + 1
+f b
+";
+
+    #[test]
+    fn test_unchanged_statements_are_not_reparsed() {
+        let mut session = Session::new(PROGRAM);
+        check(&mut session);
+        k9::snapshot!(
+            format!("{:?}", session.stats()),
+            "Stats { retokenized_lines: 7, reparsed_statements: 4 }"
+        );
+
+        replace(&mut session, "a + 1", "a + 2");
+        check(&mut session);
+        k9::snapshot!(
+            format!("{:?}", session.stats()),
+            "Stats { retokenized_lines: 1, reparsed_statements: 1 }"
+        );
+
+        replace(&mut session, "x * b", "x * a");
+        check(&mut session);
+        k9::snapshot!(
+            format!("{:?}", session.stats()),
+            "Stats { retokenized_lines: 3, reparsed_statements: 1 }"
+        );
+
+        session.parse().unwrap();
+        k9::snapshot!(session.stats().reparsed_statements, "0");
+    }
+
+    #[test]
+    fn test_statements_that_moved_are_not_reparsed() {
+        let mut session = Session::new(PROGRAM);
+        check(&mut session);
+
+        replace(&mut session, "a = 1", "# the first line\na = 1");
+        check(&mut session);
+        k9::snapshot!(
+            format!("{:?}", session.stats()),
+            "Stats { retokenized_lines: 3, reparsed_statements: 0 }"
+        );
+
+        // every symbol below the edit moved down a line
+        let (_, symbols) = session.parse_with_symbols();
+        let show_symbols = |symbols: &[crate::pos_parser::Symbol]| {
+            symbols
+                .iter()
+                .map(|symbol| {
+                    format!(
+                        "{} {}:{}",
+                        symbol.name, symbol.location.line, symbol.location.offset
+                    )
+                })
+                .collect::<Vec<_>>()
+                .join(", ")
+        };
+        k9::snapshot!(
+            show_symbols(&symbols.definitions),
+            "a 3:18, b 4:24, f 5:34, x 5:37, y 6:52"
+        );
+        k9::snapshot!(
+            show_symbols(&symbols.references),
+            "a 4:28, + 4:30, x 6:56, * 6:58, b 6:60, y 7:64, + 7:66, f 8:70, b 8:72"
+        );
+    }
+
+    #[test]
+    fn test_changing_part_of_speech_reparses_dependents() {
+        let mut session = Session::new(PROGRAM);
+        check(&mut session);
+        replace(&mut session, "b = a + 1", "b : v1 = neg");
+        k9::snapshot!(
+            check(&mut session),
+            "f (line 4): declared as n but defined as v1"
+        );
+        k9::snapshot!(session.stats().reparsed_statements, "3");
+    }
+
+    #[test]
+    fn test_edits_across_chunks() {
+        let mut session = Session::new(PROGRAM);
+        check(&mut session);
+
+        // an edit that spans the boundary between two statements
+        replace(&mut session, "1\nb", "1 + b\nc");
+        let text = session.text();
+        k9::snapshot!(text.lines().nth(2).unwrap(), "c = a + 1");
+        check(&mut session);
+
+        // indenting a statement into the body of the one before it
+        replace(&mut session, "\nf b", "\n  f b");
+        check(&mut session);
+
+        // adding and removing a statement, which changes every identifier after it
+        replace(&mut session, "", "z = 1\n");
+        check(&mut session);
+        replace(&mut session, "z = 1\n", "");
+        check(&mut session);
+    }
+
+    #[test]
+    fn test_comments() {
+        let mut session = Session::new("f =\n  x = 1\n# the result\n  x + 1\nf");
+        k9::snapshot!(
+            check(&mut session),
+            "(let ((f (let ((x 1)) (+ x 1)))) f) : n"
+        );
+        replace(&mut session, "x + 1", "x + 2");
+        check(&mut session);
+        k9::snapshot!(session.stats().retokenized_lines, "4");
+    }
+
+    #[test]
+    fn test_invalid_syntax() {
+        let mut session = Session::new(PROGRAM);
+        replace(&mut session, "b = a + 1", "b = a + 1)");
+        k9::snapshot!(check(&mut session), "invalid syntax on line 3");
+        replace(&mut session, "b = a + 1)", "b = a + 1");
+        check(&mut session);
+    }
+
+    #[test]
+    fn test_mixed_indentation() {
+        let mut session = Session::new(PROGRAM);
+        replace(&mut session, "  y + 1", "\ty + 1");
+        k9::snapshot!(
+            check(&mut session),
+            "indentation mixes tabs and spaces on line 6"
+        );
+
+        // every chunk on its own is consistent, but they don't agree
+        replace(&mut session, "\ty + 1", "  y + 1");
+        replace(&mut session, "f b", "g =\n\t1\nf b");
+        k9::snapshot!(
+            check(&mut session),
+            "indentation mixes tabs and spaces on line 8"
+        );
+    }
+
+    #[test]
+    fn test_continuation_lines() {
+        let mut session = Session::new("m = [\n1 2\n]\nm");
+        k9::snapshot!(check(&mut session), "(let ((m [1 2])) m) : n");
+
+        // the lines after an open paren become part of the same statement
+        replace(&mut session, "m = [", "m = ([");
+        replace(&mut session, "\nm", "\n+ 1)\nm");
+        k9::snapshot!(check(&mut session), "(let ((m (+ [1 2] 1))) m) : n");
+    }
+
+    #[test]
+    fn test_conditionals() {
+        let mut session = Session::new("x = 1\nif x\n  neg x\nelse\n  x\n");
+        k9::snapshot!(check(&mut session), "(let ((x 1)) (if x (neg x) x)) : n");
+
+        replace(
+            &mut session,
+            "else\n  x",
+            "else # otherwise\n  x\nif 0\n  4\nelse\n  5",
+        );
+        k9::snapshot!(
+            check(&mut session),
+            "(let ((x 1) (_ (if x (neg x) x))) (if 0 4 5)) : n"
+        );
+
+        // editing a line into an else joins it to the conditional above
+        replace(&mut session, "if 0\n  4\nelse", "if 0\n  4\nelsewhere");
+        k9::snapshot!(check(&mut session), "invalid syntax on line 7");
+        replace(&mut session, "where", "");
+        k9::snapshot!(
+            check(&mut session),
+            "(let ((x 1) (_ (if x (neg x) x))) (if 0 4 5)) : n"
+        );
+    }
+}
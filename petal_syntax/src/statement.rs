@@ -1,9 +1,103 @@
+use crate::location::Location;
+use crate::pos_parser::PartOfSpeech;
+use crate::terms::{Name, Term};
+use std::fmt;
+
 pub(super) type Block<T> = Vec<Statement<T>>;
 pub(super) type Terms<T> = Vec<T>;
 
-#[derive(Debug)]
+// An explicit part of speech for an assignment, written between the name and
+// the equal sign:
+//
+//     double : v1 = * 2
+//     twice (f : v1) : v1 = f f
+//
+// Parameters make the assignment a definition that takes operands. Declaring
+// the result up front means that the part of speech of the name is known
+// before its body has been parsed.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Signature {
+    pub(super) params: Vec<(Name, PartOfSpeech)>,
+    pub(super) result: PartOfSpeech,
+}
+
+// Written the way it appears in source: (f : v1) (x : n) : n
+impl fmt::Display for Signature {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (name, pos) in &self.params {
+            write!(f, "({} : {}) ", name, pos)?;
+        }
+        write!(f, ": {}", self.result)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
 pub(super) enum Statement<T> {
-    SimpleAssignment(String, Terms<T>),
-    CompoundAssignment(String, Block<T>),
+    SimpleAssignment(Name, Option<Signature>, Terms<T>),
+    CompoundAssignment(Name, Option<Signature>, Block<T>),
     Expression(Terms<T>),
+    // A signature without a body, which declares the part of speech of the
+    // next assignment to the same name in the same block, so that it can be
+    // used before its definition is parsed:
+    //
+    //     <+> : v2
+    //     x = 1 <+> 2
+    //     <+> = ...
+    Declaration(Name, Signature),
+    // Binds each name to the corresponding element of a tuple:
+    //
+    //     (quotient remainder) = x divmod y
+    Destructuring(Vec<Name>, Terms<T>),
+    // condition, consequent, where the else is, alternative
+    Conditional(Terms<T>, Block<T>, Location, Block<T>),
+}
+
+impl Signature {
+    fn each_location(&mut self, f: &mut impl FnMut(&mut Location)) {
+        for (name, _) in &mut self.params {
+            f(&mut name.location);
+        }
+    }
+}
+
+impl Statement<Term> {
+    // Visits every location in the statement, including the statements of any
+    // blocks inside of it.
+    pub(super) fn each_location(&mut self, f: &mut impl FnMut(&mut Location)) {
+        fn terms(terms: &mut Terms<Term>, f: &mut impl FnMut(&mut Location)) {
+            terms.iter_mut().for_each(|term| term.each_location(f))
+        }
+        fn block(block: &mut Block<Term>, f: &mut impl FnMut(&mut Location)) {
+            block
+                .iter_mut()
+                .for_each(|statement| statement.each_location(f))
+        }
+        match self {
+            Statement::SimpleAssignment(name, signature, body) => {
+                f(&mut name.location);
+                signature.iter_mut().for_each(|s| s.each_location(f));
+                terms(body, f);
+            }
+            Statement::CompoundAssignment(name, signature, body) => {
+                f(&mut name.location);
+                signature.iter_mut().for_each(|s| s.each_location(f));
+                block(body, f);
+            }
+            Statement::Expression(body) => terms(body, f),
+            Statement::Declaration(name, signature) => {
+                f(&mut name.location);
+                signature.each_location(f);
+            }
+            Statement::Destructuring(names, body) => {
+                names.iter_mut().for_each(|name| f(&mut name.location));
+                terms(body, f);
+            }
+            Statement::Conditional(condition, consequent, otherwise, alternative) => {
+                terms(condition, f);
+                block(consequent, f);
+                f(otherwise);
+                block(alternative, f);
+            }
+        }
+    }
 }
@@ -1,7 +1,8 @@
 use crate::helpers::*;
 use crate::located_token::*;
+use crate::pos_parser::{Arity, ParseError, PartOfSpeech};
 use crate::statement::*;
-use crate::terms::SemiSoupyTerm;
+use crate::terms::{Delimiters, Name, SemiSoupyTerm};
 use crate::token::*;
 use crate::tokens::*;
 use nom::{
@@ -9,7 +10,7 @@ use nom::{
     bytes::complete::take,
     combinator::{eof, map, map_opt, opt, verify},
     multi::{many0, many1},
-    sequence::delimited,
+    sequence::{delimited, preceded, tuple},
     IResult,
 };
 
@@ -31,23 +32,39 @@ fn identifier(i: Tokens) -> ParseResult<String> {
     })(i)
 }
 
-fn numeric_literal(i: Tokens) -> ParseResult<String> {
+fn name(i: Tokens) -> ParseResult<Name> {
     map_opt(any_token, |t: &LocatedToken| match &t.token {
-        Token::NumericLiteral(x) => Some(x.to_string()),
+        Token::Identifier(x) => Some(Name {
+            name: x.to_string(),
+            location: t.location,
+        }),
         _ => None,
     })(i)
 }
 
-fn punctuation_soup(i: Tokens) -> ParseResult<String> {
+fn numeric_literal(i: Tokens) -> ParseResult<Name> {
     map_opt(any_token, |t: &LocatedToken| match &t.token {
-        Token::PunctuationSoup(x) => Some(x.to_string()),
+        Token::NumericLiteral(x) => Some(Name {
+            name: x.to_string(),
+            location: t.location,
+        }),
         _ => None,
     })(i)
 }
 
-fn semicolons(i: Tokens) -> ParseResult<usize> {
+fn punctuation_soup(i: Tokens) -> ParseResult<Name> {
     map_opt(any_token, |t: &LocatedToken| match &t.token {
-        Token::Semicolons(x) => Some(*x),
+        Token::PunctuationSoup(x) => Some(Name {
+            name: x.to_string(),
+            location: t.location,
+        }),
+        _ => None,
+    })(i)
+}
+
+fn semicolons(i: Tokens) -> ParseResult<SemiSoupyTerm> {
+    map_opt(any_token, |t: &LocatedToken| match &t.token {
+        Token::Semicolons(x) => Some(SemiSoupyTerm::Semicolons(*x, t.location)),
         _ => None,
     })(i)
 }
@@ -64,39 +81,43 @@ fn maybe_space(i: Tokens) -> UnitResult {
     ignore(opt(skip_token(Token::Space)))(i)
 }
 
-// TODO: theoretically this should allow indent/outdent so that you could write
-// expressions like:
-//
-//     x = (1 +
-//          2)
-//
-// And replace newlines with spaces and stuff.
+// Line breaks inside parens and brackets are already spaces by the time they
+// get here -- see tokenize_lines.
 fn inner_expressions(i: Tokens) -> ParseResult<Terms<SemiSoupyTerm>> {
     many0(term)(i)
 }
 
+// The terms between a pair of delimiters, and where the delimiters are.
+fn delimited_terms<'a>(
+    open: Token,
+    close: Token,
+) -> impl FnMut(Tokens<'a>) -> ParseResult<'a, (Terms<SemiSoupyTerm>, Delimiters)> {
+    map(
+        tuple((match_token(open), inner_expressions, match_token(close))),
+        |(open, terms, close)| {
+            let delimiters = Delimiters {
+                open: open.location,
+                close: close.location,
+            };
+            (terms, delimiters)
+        },
+    )
+}
+
 fn term(i: Tokens) -> ParseResult<SemiSoupyTerm> {
     alt((
-        map(identifier, SemiSoupyTerm::Identifier),
+        map(name, SemiSoupyTerm::Identifier),
         map(numeric_literal, SemiSoupyTerm::NumericLiteral),
         map(punctuation_soup, SemiSoupyTerm::PunctuationSoup),
-        map(semicolons, SemiSoupyTerm::Semicolons),
+        semicolons,
         replace(match_token(Token::Space), SemiSoupyTerm::Space),
         map(
-            delimited(
-                match_token(Token::OpenParen),
-                inner_expressions,
-                match_token(Token::CloseParen),
-            ),
-            SemiSoupyTerm::Parens,
+            delimited_terms(Token::OpenParen, Token::CloseParen),
+            |(terms, delimiters)| SemiSoupyTerm::Parens(terms, delimiters),
         ),
         map(
-            delimited(
-                match_token(Token::OpenBracket),
-                inner_expressions,
-                match_token(Token::CloseBracket),
-            ),
-            SemiSoupyTerm::Brackets,
+            delimited_terms(Token::OpenBracket, Token::CloseBracket),
+            |(terms, delimiters)| SemiSoupyTerm::Brackets(terms, delimiters),
         ),
     ))(i)
 }
@@ -105,8 +126,41 @@ fn expression(i: Tokens) -> ParseResult<Terms<SemiSoupyTerm>> {
     many1(term)(i)
 }
 
+fn part_of_speech(i: Tokens) -> ParseResult<PartOfSpeech> {
+    map_opt(identifier, |name| match name.as_str() {
+        "n" => Some(PartOfSpeech::Noun),
+        "v1" => Some(PartOfSpeech::Verb(Arity::Unary)),
+        "v2" => Some(PartOfSpeech::Verb(Arity::Binary)),
+        _ => None,
+    })(i)
+}
+
+fn annotation(i: Tokens) -> ParseResult<PartOfSpeech> {
+    let (i, ()) = maybe_space(i)?;
+    let (i, ()) = skip_token(Token::PunctuationSoup(":".to_string()))(i)?;
+    let (i, ()) = maybe_space(i)?;
+    part_of_speech(i)
+}
+
+fn parameter(i: Tokens) -> ParseResult<(Name, PartOfSpeech)> {
+    delimited(
+        match_token(Token::OpenParen),
+        tuple((name, annotation)),
+        match_token(Token::CloseParen),
+    )(i)
+}
+
+// (f : v1) (g : v2) : v1
+fn signature(i: Tokens) -> ParseResult<Signature> {
+    let (i, params) = many0(preceded(skip_token(Token::Space), parameter))(i)?;
+    let (i, result) = annotation(i)?;
+    Ok((i, Signature { params, result }))
+}
+
 fn assignment_statement(i: Tokens) -> ParseResult<Statement<SemiSoupyTerm>> {
-    let (i, identifier) = identifier(i)?;
+    // operators are defined just like anything else
+    let (i, target) = alt((name, punctuation_soup))(i)?;
+    let (i, signature) = opt(signature)(i)?;
     let (i, ()) = maybe_space(i)?;
     let (i, ()) = skip_token(Token::EqualSign)(i)?;
     let (i, ()) = maybe_space(i)?;
@@ -118,9 +172,12 @@ fn assignment_statement(i: Tokens) -> ParseResult<Statement<SemiSoupyTerm>> {
         if let Some(expression) = expression {
             block.insert(0, Statement::Expression(expression));
         };
-        Ok((i, Statement::CompoundAssignment(identifier, block)))
+        Ok((i, Statement::CompoundAssignment(target, signature, block)))
     } else if let Some(expression) = expression {
-        Ok((i, Statement::SimpleAssignment(identifier, expression)))
+        Ok((
+            i,
+            Statement::SimpleAssignment(target, signature, expression),
+        ))
     } else {
         // TODO: should be a custom error type
         Err(nom::Err::Error(nom::error::Error::new(
@@ -130,14 +187,82 @@ fn assignment_statement(i: Tokens) -> ParseResult<Statement<SemiSoupyTerm>> {
     }
 }
 
+// <+> : v2
+fn declaration_statement(i: Tokens) -> ParseResult<Statement<SemiSoupyTerm>> {
+    let (i, target) = alt((name, punctuation_soup))(i)?;
+    let (i, signature) = signature(i)?;
+    let (i, ()) = maybe_space(i)?;
+    let (i, ()) = skip_token(Token::Newline)(i)?;
+    Ok((i, Statement::Declaration(target, signature)))
+}
+
+// (a b) = f x
+fn destructuring_statement(i: Tokens) -> ParseResult<Statement<SemiSoupyTerm>> {
+    let (i, ()) = skip_token(Token::OpenParen)(i)?;
+    let (i, ()) = maybe_space(i)?;
+    let (i, first) = name(i)?;
+    let (i, rest) = many1(preceded(skip_token(Token::Space), name))(i)?;
+    let (i, ()) = maybe_space(i)?;
+    let (i, ()) = skip_token(Token::CloseParen)(i)?;
+    let (i, ()) = maybe_space(i)?;
+    let (i, ()) = skip_token(Token::EqualSign)(i)?;
+    let (i, ()) = maybe_space(i)?;
+    let (i, expression) = expression(i)?;
+    let (i, ()) = skip_token(Token::Newline)(i)?;
+    let mut names = vec![first];
+    names.extend(rest);
+    Ok((i, Statement::Destructuring(names, expression)))
+}
+
 fn expression_statement(i: Tokens) -> ParseResult<Statement<SemiSoupyTerm>> {
     let (i, expression) = map(expression, Statement::Expression)(i)?;
     let (i, ()) = skip_token(Token::Newline)(i)?;
     Ok((i, expression))
 }
 
+fn keyword<'a>(name: &str) -> impl FnMut(Tokens<'a>) -> UnitResult {
+    skip_token(Token::Identifier(name.to_string()))
+}
+
+fn indented_block(i: Tokens) -> ParseResult<Block<SemiSoupyTerm>> {
+    let (i, ()) = skip_token(Token::Newline)(i)?;
+    let (i, ()) = skip_token(Token::Indent)(i)?;
+    statements(i)
+}
+
+// if condition
+//   consequent
+// else if other condition
+//   alternative
+// else
+//   alternative
+fn conditional_statement(i: Tokens) -> ParseResult<Statement<SemiSoupyTerm>> {
+    let (i, ()) = keyword("if")(i)?;
+    let (i, ()) = skip_token(Token::Space)(i)?;
+    let (i, condition) = expression(i)?;
+    let (i, consequent) = indented_block(i)?;
+    let (i, otherwise) = match_token(Token::Identifier("else".to_string()))(i)?;
+    let (i, alternative) = alt((
+        indented_block,
+        preceded(
+            skip_token(Token::Space),
+            map(conditional_statement, |statement| vec![statement]),
+        ),
+    ))(i)?;
+    Ok((
+        i,
+        Statement::Conditional(condition, consequent, otherwise.location, alternative),
+    ))
+}
+
 fn statement(i: Tokens) -> ParseResult<Statement<SemiSoupyTerm>> {
-    alt((assignment_statement, expression_statement))(i)
+    alt((
+        conditional_statement,
+        assignment_statement,
+        declaration_statement,
+        destructuring_statement,
+        expression_statement,
+    ))(i)
 }
 
 // separated by newlines until either outdent or EOF reached
@@ -159,21 +284,48 @@ pub(super) fn parse_expression(tokens: Vec<LocatedToken>) -> Result<Terms<SemiSo
     }
 }
 
+// Points at the first token that isn't part of a statement if the tokens don't
+// parse, or at the second binding of a name that a destructuring assignment
+// binds twice. A program with no tokens at all has no statements.
 pub(super) fn parse_tokens(
     tokens: Vec<LocatedToken>,
-) -> Result<Vec<Statement<SemiSoupyTerm>>, String> {
-    let tokens = Tokens::new(&tokens);
-
-    match statements(tokens) {
-        Ok((remaining, block)) => {
-            if remaining.is_empty() {
-                Ok(block)
-            } else {
-                Err(format!("parse was not total. remaining: {:?}", remaining))
+) -> Result<Vec<Statement<SemiSoupyTerm>>, ParseError> {
+    let end = match tokens.last() {
+        Some(token) => token.location,
+        None => return Ok(vec![]),
+    };
+    let unparsed = match statements(Tokens::new(&tokens)) {
+        Ok((remaining, block)) if remaining.is_empty() => {
+            return match bound_twice(&block) {
+                Some(name) => Err(ParseError::BoundTwice(name.name.clone(), name.location)),
+                None => Ok(block),
             }
         }
-        Err(e) => Err(format!("{}", e)),
-    }
+        Ok((remaining, _)) => remaining,
+        Err(nom::Err::Error(e) | nom::Err::Failure(e)) => e.input,
+        Err(nom::Err::Incomplete(_)) => Tokens::new(&[]),
+    };
+    let location = unparsed.0.first().map_or(end, |token| token.location);
+    Err(ParseError::InvalidSyntax(location))
+}
+
+// The first name that a destructuring assignment in the block binds more than
+// once.
+fn bound_twice(block: &Block<SemiSoupyTerm>) -> Option<&Name> {
+    block.iter().find_map(|statement| match statement {
+        Statement::Destructuring(names, _) => names
+            .iter()
+            .enumerate()
+            .find(|(n, name)| names[..*n].iter().any(|earlier| earlier.name == name.name))
+            .map(|(_, name)| name),
+        Statement::CompoundAssignment(_, _, block) => bound_twice(block),
+        Statement::Conditional(_, consequent, _, alternative) => {
+            bound_twice(consequent).or_else(|| bound_twice(alternative))
+        }
+        Statement::SimpleAssignment(_, _, _)
+        | Statement::Expression(_)
+        | Statement::Declaration(_, _) => None,
+    })
 }
 
 #[cfg(test)]
@@ -200,14 +352,46 @@ mod tests {
 
     fn show_statement(statement: &Statement<SemiSoupyTerm>) -> String {
         match statement {
-            Statement::SimpleAssignment(id, expr) => format!("{}={}", id, show_expression(expr)),
-            Statement::CompoundAssignment(id, block) => format!("{}={{{}}}", id, show_block(block)),
+            Statement::SimpleAssignment(id, sig, expr) => {
+                format!("{}{}={}", id, show_signature(sig), show_expression(expr))
+            }
+            Statement::CompoundAssignment(id, sig, block) => {
+                format!("{}{}={{{}}}", id, show_signature(sig), show_block(block))
+            }
             Statement::Expression(expr) => show_expression(expr),
+            Statement::Declaration(id, sig) => {
+                format!("{}{}", id, show_signature(&Some(sig.clone())))
+            }
+            Statement::Destructuring(names, expr) => {
+                let names = names.iter().map(|name| name.to_string());
+                let names = names.collect::<Vec<_>>().join(" ");
+                format!("({})={}", names, show_expression(expr))
+            }
+            Statement::Conditional(condition, consequent, _, alternative) => format!(
+                "if {} {{{}}} else {{{}}}",
+                show_expression(condition),
+                show_block(consequent),
+                show_block(alternative)
+            ),
+        }
+    }
+
+    fn show_signature(signature: &Option<Signature>) -> String {
+        match signature {
+            None => String::new(),
+            Some(Signature { params, result }) => {
+                let params = params
+                    .iter()
+                    .map(|(name, pos)| format!("({}:{})", name, pos))
+                    .collect::<Vec<_>>()
+                    .join("");
+                format!("{}:{}", params, result)
+            }
         }
     }
 
     fn test(input: &str) -> String {
-        let tokens = tokenize(input);
+        let tokens = tokenize(input).unwrap();
         let tokens = Tokens::new(&tokens);
 
         match statements(tokens) {
@@ -224,6 +408,42 @@ mod tests {
         k9::snapshot!(test("foo = bar"), "foo=bar");
     }
 
+    #[test]
+    fn operator_assignment() {
+        k9::snapshot!(test("<+> = +"), "<+>=+");
+        k9::snapshot!(
+            test("<+> (x : n) (y : n) : n = x + y"),
+            "<+>(x:n)(y:n):n=x ␠ + ␠ y"
+        );
+        k9::snapshot!(test("- 1"), "- ␠ 1");
+    }
+
+    #[test]
+    fn declaration() {
+        k9::snapshot!(test("<+> : v2"), "<+>:v2");
+        k9::snapshot!(test("twice (f : v1) : v1"), "twice(f:v1):v1");
+        k9::snapshot!(test("<+> : v2\n1 <+> 2"), "<+>:v2; 1 ␠ <+> ␠ 2");
+        // without a part of speech, it's just an expression
+        k9::snapshot!(test("x :"), "x ␠ :");
+    }
+
+    #[test]
+    fn destructuring() {
+        k9::snapshot!(test("(a b) = f x"), "(a b)=f ␠ x");
+        k9::snapshot!(test("( a b c )=x"), "(a b c)=x");
+        // the error points at the second a
+        k9::snapshot!(
+            parse_tokens(tokenize("(a b a) = x").unwrap()).unwrap_err(),
+            r#"BoundTwice("a", Location { offset: 5, line: 1 })"#
+        );
+        k9::snapshot!(
+            parse_tokens(tokenize("f =\n  (a a) = x\n  a\nf").unwrap())
+                .unwrap_err()
+                .to_string(),
+            "a is bound twice on line 2"
+        );
+    }
+
     #[test]
     fn compound_assignment() {
         k9::snapshot!(
@@ -268,6 +488,79 @@ foo =
         );
     }
 
+    #[test]
+    fn signatures() {
+        k9::snapshot!(test("double : v1 = * 2"), "double:v1=* ␠ 2");
+        k9::snapshot!(test("double: v1 = * 2"), "double:v1=* ␠ 2");
+        k9::snapshot!(test("twice (f : v1) : v1 = f f"), "twice(f:v1):v1=f ␠ f");
+        k9::snapshot!(
+            test("under (f:v1) (g:v1): v1 = g f g"),
+            "under(f:v1)(g:v1):v1=g ␠ f ␠ g"
+        );
+        k9::snapshot!(
+            test(
+                "
+twice (f : v1) : v1 =
+  g = f
+  g g
+"
+            ),
+            "twice(f:v1):v1={g=f; g ␠ g}"
+        );
+    }
+
+    #[test]
+    fn signature_errors() {
+        k9::snapshot!(
+            test("twice (f : v1) = f f"),
+            r#"Parsing Error: Error { input: Tokens([LocatedToken { location: Location { offset: 15, line: 1 }, token: EqualSign }, LocatedToken { location: Location { offset: 16, line: 1 }, token: Space }, LocatedToken { location: Location { offset: 17, line: 1 }, token: Identifier("f") }, LocatedToken { location: Location { offset: 18, line: 1 }, token: Space }, LocatedToken { location: Location { offset: 19, line: 1 }, token: Identifier("f") }, LocatedToken { location: Location { offset: 20, line: 1 }, token: Newline }]), code: Verify }"#
+        );
+    }
+
+    #[test]
+    fn conditionals() {
+        k9::snapshot!(
+            test(
+                "
+if x < 0
+  neg x
+else
+  x
+"
+            ),
+            "if x ␠ < ␠ 0 {neg ␠ x} else {x}"
+        );
+
+        k9::snapshot!(
+            test(
+                "
+sign =
+  if x < 0
+    -1
+  else if x > 0
+    1
+  else
+    y = 0
+    y
+"
+            ),
+            "sign={if x ␠ < ␠ 0 {-1} else {if x ␠ > ␠ 0 {1} else {y=0; y}}}"
+        );
+    }
+
+    #[test]
+    fn conditional_without_alternative() {
+        k9::snapshot!(
+            test(
+                "
+if x
+  y
+"
+            ),
+            r#"Parsing Error: Error { input: Tokens([LocatedToken { location: Location { offset: 6, line: 3 }, token: Indent }, LocatedToken { location: Location { offset: 8, line: 3 }, token: Identifier("y") }, LocatedToken { location: Location { offset: 9, line: 3 }, token: Newline }, LocatedToken { location: Location { offset: 9, line: 3 }, token: Outdent }]), code: Eof }"#
+        );
+    }
+
     #[test]
     fn parse_errors() {
         k9::snapshot!(
@@ -0,0 +1,94 @@
+// The number of single-character insertions, deletions, substitutions, or
+// swaps of adjacent characters it takes to turn one string into the other.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a = a.chars().collect::<Vec<_>>();
+    let b = b.chars().collect::<Vec<_>>();
+    // distances[i][j] is the distance between the first i characters of a and
+    // the first j characters of b
+    let mut distances = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in distances.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    for (j, distance) in distances[0].iter_mut().enumerate() {
+        *distance = j;
+    }
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let substitution = distances[i - 1][j - 1] + (a[i - 1] != b[j - 1]) as usize;
+            let deletion = distances[i - 1][j] + 1;
+            let insertion = distances[i][j - 1] + 1;
+            let mut distance = substitution.min(deletion).min(insertion);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                distance = distance.min(distances[i - 2][j - 2] + 1);
+            }
+            distances[i][j] = distance;
+        }
+    }
+    distances[a.len()][b.len()]
+}
+
+const MAX_SUGGESTIONS: usize = 3;
+
+fn is_identifier(name: &str) -> bool {
+    name.starts_with(crate::tokenizer::is_initial_identifier_character)
+}
+
+// The candidates that are close enough to name that they might be what the
+// user meant to type, closest first. Longer names are allowed more typos, but
+// a candidate that differs in every character isn't close to anything, and an
+// identifier is never a typo for an operator or the other way around.
+pub(super) fn suggestions<'a>(
+    name: &str,
+    candidates: impl IntoIterator<Item = &'a str>,
+) -> Vec<String> {
+    let length = name.chars().count();
+    let max_distance = (length / 3).max(1);
+    let mut suggestions = candidates
+        .into_iter()
+        .filter(|candidate| *candidate != name && *candidate != "_")
+        .filter(|candidate| is_identifier(candidate) == is_identifier(name))
+        .map(|candidate| (edit_distance(name, candidate), candidate))
+        .filter(|(distance, _)| *distance <= max_distance && *distance < length)
+        .collect::<Vec<_>>();
+    suggestions.sort();
+    suggestions.dedup();
+    suggestions
+        .into_iter()
+        .take(MAX_SUGGESTIONS)
+        .map(|(_, candidate)| candidate.to_string())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_edit_distance() {
+        k9::snapshot!(edit_distance("", ""), "0");
+        k9::snapshot!(edit_distance("foo", "foo"), "0");
+        k9::snapshot!(edit_distance("foo", "fo"), "1");
+        k9::snapshot!(edit_distance("fo", "foo"), "1");
+        k9::snapshot!(edit_distance("foo", "boo"), "1");
+        k9::snapshot!(edit_distance("nge", "neg"), "1");
+        k9::snapshot!(edit_distance("kitten", "sitting"), "3");
+    }
+
+    #[test]
+    fn test_suggestions() {
+        let candidates = ["foo", "for", "bar", "fold", "abs", "_"];
+        k9::snapshot!(suggestions("fo", candidates).join(" "), "foo for");
+        k9::snapshot!(suggestions("fol", candidates).join(" "), "fold foo for");
+        k9::snapshot!(suggestions("xyz", candidates), "[]");
+        k9::snapshot!(suggestions("bs", candidates).join(" "), "abs");
+    }
+
+    #[test]
+    fn test_one_letter_names() {
+        let candidates = ["x", "xs", "+", "*", "%"];
+        k9::snapshot!(suggestions("y", candidates), "[]");
+        k9::snapshot!(suggestions("x", candidates), "[]");
+        k9::snapshot!(suggestions("-", candidates), "[]");
+        k9::snapshot!(suggestions("++", candidates).join(" "), "+");
+    }
+}
@@ -1,5 +1,28 @@
+use crate::location::Location;
 use std::fmt;
 
+// An identifier, operator, or numeric literal, and where it appears in the
+// source.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Name {
+    pub(super) name: String,
+    pub(super) location: Location,
+}
+
+impl fmt::Display for Name {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)
+    }
+}
+
+// Where a pair of parens or brackets opens and closes in the source. The
+// parens and brackets that later passes add span the terms inside of them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Delimiters {
+    pub(super) open: Location,
+    pub(super) close: Location,
+}
+
 // We go through multiple parsing passes, changing the type of terms each time.
 //
 // First we create SemiSoupyTerms, which do not know how to split sequences of
@@ -18,43 +41,65 @@ use std::fmt;
 // parse output.
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub(super) enum SemiSoupyTerm {
-    Identifier(String),
-    PunctuationSoup(String),
-    NumericLiteral(String),
-    Parens(Vec<SemiSoupyTerm>),
-    Brackets(Vec<SemiSoupyTerm>),
-    Semicolons(usize),
+    Identifier(Name),
+    PunctuationSoup(Name),
+    NumericLiteral(Name),
+    Parens(Vec<SemiSoupyTerm>, Delimiters),
+    Brackets(Vec<SemiSoupyTerm>, Delimiters),
+    Semicolons(usize, Location),
     Space,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub(super) enum SoupyTerm {
-    Identifier(String),
-    PunctuationSoup(String),
-    NumericLiteral(String),
-    Parens(Vec<SoupyTerm>),
-    Brackets(Vec<SoupyTerm>),
+    Identifier(Name),
+    PunctuationSoup(Name),
+    NumericLiteral(Name),
+    Parens(Vec<SoupyTerm>, Delimiters),
+    Brackets(Vec<SoupyTerm>, Delimiters),
     Space,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub(super) enum SouplessTerm {
-    Identifier(String),
-    Operator(String),
-    MinusOperator,
-    NumericLiteral(String),
-    Parens(Vec<SouplessTerm>),
-    Brackets(Vec<SouplessTerm>),
+    Identifier(Name),
+    Operator(Name),
+    MinusOperator(Location),
+    // punctuation that can't be split into operators that are in scope, and
+    // the ways that it could have been split if it had spaces in it
+    UnknownOperator(Name, Vec<String>),
+    NumericLiteral(Name),
+    Parens(Vec<SouplessTerm>, Delimiters),
+    Brackets(Vec<SouplessTerm>, Delimiters),
     Space,
 }
 
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub(super) enum Term {
-    Identifier(String),
-    NumericLiteral(String),
-    Coefficient(String),
-    Parens(Vec<Term>),
-    Brackets(Vec<Term>),
+    Identifier(Name),
+    NumericLiteral(Name),
+    // the scale factor, and the token that it came from
+    Coefficient(String, Name),
+    UnknownOperator(Name, Vec<String>),
+    Parens(Vec<Term>, Delimiters),
+    Brackets(Vec<Term>, Delimiters),
+}
+
+impl Term {
+    // Visits every location in the term, so that they can all be moved at once.
+    pub(super) fn each_location(&mut self, f: &mut impl FnMut(&mut Location)) {
+        match self {
+            Term::Identifier(name)
+            | Term::NumericLiteral(name)
+            | Term::Coefficient(_, name)
+            | Term::UnknownOperator(name, _) => f(&mut name.location),
+            Term::Parens(terms, delimiters) | Term::Brackets(terms, delimiters) => {
+                f(&mut delimiters.open);
+                terms.iter_mut().for_each(|term| term.each_location(f));
+                f(&mut delimiters.close);
+            }
+        }
+    }
 }
 
 fn listed<T: fmt::Display>(terms: &[T], f: &mut fmt::Formatter) -> fmt::Result {
@@ -74,19 +119,20 @@ impl fmt::Display for SemiSoupyTerm {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use SemiSoupyTerm::*;
         match self {
-            Parens(terms) => {
+            Parens(terms, _) => {
                 write!(f, "(")?;
                 listed(terms, f)?;
                 write!(f, ")")
             }
-            Brackets(terms) => {
+            Brackets(terms, _) => {
                 write!(f, "[")?;
                 listed(terms, f)?;
                 write!(f, "]")
             }
             Space => write!(f, "␠"),
-            Semicolons(count) => write!(f, "{}", ";".repeat(*count)),
-            Identifier(s) | PunctuationSoup(s) | NumericLiteral(s) => write!(f, "{}", s),
+            Semicolons(count, _) => write!(f, "{}", ";".repeat(*count)),
+            Identifier(name) | PunctuationSoup(name) => write!(f, "{}", name),
+            NumericLiteral(s) => write!(f, "{}", s),
         }
     }
 }
@@ -95,18 +141,62 @@ impl fmt::Display for SoupyTerm {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         use SoupyTerm::*;
         match self {
-            Parens(terms) => {
+            Parens(terms, _) => {
+                write!(f, "(")?;
+                listed(terms, f)?;
+                write!(f, ")")
+            }
+            Brackets(terms, _) => {
+                write!(f, "[")?;
+                listed(terms, f)?;
+                write!(f, "]")
+            }
+            Space => write!(f, "␠"),
+            Identifier(name) | PunctuationSoup(name) => write!(f, "{}", name),
+            NumericLiteral(s) => write!(f, "{}", s),
+        }
+    }
+}
+
+impl fmt::Display for SouplessTerm {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use SouplessTerm::*;
+        match self {
+            Parens(terms, _) => {
                 write!(f, "(")?;
                 listed(terms, f)?;
                 write!(f, ")")
             }
-            Brackets(terms) => {
+            Brackets(terms, _) => {
                 write!(f, "[")?;
                 listed(terms, f)?;
                 write!(f, "]")
             }
             Space => write!(f, "␠"),
-            Identifier(s) | PunctuationSoup(s) | NumericLiteral(s) => write!(f, "{}", s),
+            MinusOperator(_) => write!(f, "-"),
+            UnknownOperator(soup, _) => write!(f, "<unknown {}>", soup),
+            Identifier(name) | Operator(name) | NumericLiteral(name) => write!(f, "{}", name),
+        }
+    }
+}
+
+impl fmt::Display for Term {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        use Term::*;
+        match self {
+            Parens(terms, _) => {
+                write!(f, "(")?;
+                listed(terms, f)?;
+                write!(f, ")")
+            }
+            Brackets(terms, _) => {
+                write!(f, "[")?;
+                listed(terms, f)?;
+                write!(f, "]")
+            }
+            Coefficient(scale, _) => write!(f, "<scale {}>", scale),
+            UnknownOperator(soup, _) => write!(f, "<unknown {}>", soup),
+            Identifier(name) | NumericLiteral(name) => write!(f, "{}", name),
         }
     }
 }
@@ -1,5 +1,7 @@
 use crate::helpers::*;
 use crate::located_token::*;
+use crate::location::Location;
+use crate::pos_parser::ParseError;
 use crate::span::*;
 use crate::token::*;
 use nom::{
@@ -13,11 +15,11 @@ use nom::{
 };
 use std::cmp::Ordering;
 
-fn is_initial_identifier_character(c: char) -> bool {
+pub(super) fn is_initial_identifier_character(c: char) -> bool {
     c.is_alphabetic() || c == '_'
 }
 
-fn is_identifier_character(c: char) -> bool {
+pub(super) fn is_identifier_character(c: char) -> bool {
     is_initial_identifier_character(c) || c.is_numeric()
 }
 
@@ -140,28 +142,98 @@ fn eol(i: Span) -> IResult<Span, ()> {
     ignore(alt((line_ending, eof)))(i)
 }
 
-pub(super) fn tokenize_lines(i: Span) -> IResult<Span, Vec<LocatedToken>> {
+// from # to the end of the line
+fn comment(i: Span) -> IResult<Span, ()> {
+    ignore(tuple((char('#'), take_while(|c| c != '\n' && c != '\r'))))(i)
+}
+
+// Lines with nothing but whitespace and comments on them, which don't affect
+// indentation at all, so that editors that leave trailing whitespace around
+// don't interfere with it.
+fn blank_line(i: Span) -> IResult<Span, ()> {
+    ignore(tuple((space0, opt(comment), eol)))(i)
+}
+
+// You can indent with tabs or with spaces, but not both: there's no way to
+// know how wide a tab is supposed to be.
+fn check_indentation(spaces: Span, indentation: &mut Option<char>) -> Result<(), ParseError> {
+    for c in spaces.fragment().chars() {
+        match indentation {
+            None => *indentation = Some(c),
+            Some(expected) if *expected != c => {
+                return Err(ParseError::MixedIndentation(Location::of_span(&spaces)))
+            }
+            Some(_) => (),
+        }
+    }
+    Ok(())
+}
+
+// The character that the first indented line is indented with, and where.
+// Everything after it has to be indented with the same character.
+pub(super) fn first_indentation(input: &str, tokens: &[LocatedToken]) -> Option<(char, Location)> {
+    let indent = tokens.iter().find(|token| token.token == Token::Indent)?;
+    let c = input[indent.location.offset..].chars().next()?;
+    Some((c, indent.location))
+}
+
+// The tokenizer doesn't know what went wrong, only where.
+fn invalid_syntax(error: nom::Err<nom::error::Error<Span>>) -> ParseError {
+    match error {
+        nom::Err::Error(e) | nom::Err::Failure(e) => {
+            ParseError::InvalidSyntax(Location::of_span(&e.input))
+        }
+        nom::Err::Incomplete(_) => unreachable!("the tokenizer only uses complete parsers"),
+    }
+}
+
+// The number of parens and brackets that are still open after these tokens.
+// Unmatched closing delimiters are left for the statement parser to complain
+// about.
+pub(super) fn open_delimiters(depth: usize, tokens: &[LocatedToken]) -> usize {
+    tokens.iter().fold(depth, |depth, token| match token.token {
+        Token::OpenParen | Token::OpenBracket => depth + 1,
+        Token::CloseParen | Token::CloseBracket => depth.saturating_sub(1),
+        _ => depth,
+    })
+}
+
+// Lines that end inside parens or brackets continue onto the next line, which
+// can be indented however you like:
+//
+//     x = (1 +
+//          2)
+//
+// The line break and the indentation of the next line become a single space,
+// and continuation lines don't affect the indentation of the block.
+pub(super) fn tokenize_lines(i: Span) -> Result<Vec<LocatedToken>, ParseError> {
     let mut result = Vec::new();
     let mut indentation_stack: Vec<usize> = vec![0];
+    let mut indentation = None;
+    let mut depth = 0;
 
     let mut remaining = i;
 
     while !remaining.is_empty() {
         let i = remaining;
 
-        // skip over completely blank lines, so that editors that trim trailing
-        // whitespace don't interfere with indentation.
-        // TODO: should this also skip over lines that consist only of
-        // whitespace? probably, right?
-        if let Ok((i, ())) = eol(i) {
+        if let Ok((i, ())) = blank_line(i) {
             remaining = i;
             continue;
         }
 
-        let (i, spaces) = recognize(space0)(i)?;
+        let (i, spaces) = recognize(space0)(i).map_err(invalid_syntax)?;
+        if depth > 0 {
+            let (i, tokens) = tokens(i).map_err(invalid_syntax)?;
+            depth = open_delimiters(depth, &tokens);
+            result.extend(tokens);
+            let (i, ()) = end_line(i, depth, &mut result).map_err(invalid_syntax)?;
+            remaining = i;
+            continue;
+        }
+        check_indentation(spaces, &mut indentation)?;
         let this_indentation = spaces.len();
         let previous_indentation = *indentation_stack.last().unwrap();
-        // TODO: this shouldn't push indent/outdent for blank lines
         match this_indentation.cmp(&previous_indentation) {
             Ordering::Greater => {
                 indentation_stack.push(this_indentation);
@@ -171,11 +243,9 @@ pub(super) fn tokenize_lines(i: Span) -> IResult<Span, Vec<LocatedToken>> {
                 let candidate = *indentation_stack.last().unwrap();
                 match candidate.cmp(&this_indentation) {
                     Ordering::Less => {
-                        // TODO: this should return a custom error for an illegal outdent
-                        return Err(nom::Err::Error(nom::error::Error::<Span>::new(
-                            i,
-                            nom::error::ErrorKind::Fail,
-                        )));
+                        // TODO: this should be a more specific error for an
+                        // illegal outdent
+                        return Err(ParseError::InvalidSyntax(Location::of_span(&i)));
                     }
                     Ordering::Greater => {
                         result.push(LocatedToken::of_span(spaces, Token::Outdent));
@@ -186,12 +256,10 @@ pub(super) fn tokenize_lines(i: Span) -> IResult<Span, Vec<LocatedToken>> {
             },
             Ordering::Equal => (),
         }
-        let (i, tokens) = tokens(i)?;
+        let (i, tokens) = tokens(i).map_err(invalid_syntax)?;
+        depth = open_delimiters(depth, &tokens);
         result.extend(tokens);
-
-        // we always add a newline, even if it isn't present in the source
-        let (i, eol) = recognize(eol)(i)?;
-        result.push(LocatedToken::of_span(eol, Token::Newline));
+        let (i, ()) = end_line(i, depth, &mut result).map_err(invalid_syntax)?;
         remaining = i;
     }
 
@@ -203,18 +271,33 @@ pub(super) fn tokenize_lines(i: Span) -> IResult<Span, Vec<LocatedToken>> {
         result.push(LocatedToken::new(eof, Token::Outdent))
     }
 
-    Ok((remaining, result))
+    Ok(result)
 }
 
-// TODO: comments, string literals, etc. certain types of comments are actually
-// significant...
-pub(super) fn tokenize(i: &str) -> Vec<LocatedToken> {
-    let i = new_span(i);
-    let (remaining, tokens) = tokenize_lines(i).unwrap();
-    if !remaining.is_empty() {
-        panic!("tokenize error, remaining: {:?}", remaining);
+// We always add a newline, even if it isn't present in the source, unless the
+// line continues onto the next one.
+fn end_line<'a>(
+    i: Span<'a>,
+    depth: usize,
+    result: &mut Vec<LocatedToken>,
+) -> IResult<Span<'a>, ()> {
+    let (i, _) = opt(comment)(i)?;
+    let (i, eol) = recognize(eol)(i)?;
+    if depth == 0 || i.is_empty() {
+        // trailing whitespace doesn't mean anything
+        if matches!(result.last(), Some(token) if token.token == Token::Space) {
+            result.pop();
+        }
+        result.push(LocatedToken::of_span(eol, Token::Newline));
+    } else if !matches!(result.last(), Some(token) if token.token == Token::Space) {
+        result.push(LocatedToken::of_span(eol, Token::Space));
     }
-    tokens
+    Ok((i, ()))
+}
+
+// TODO: string literals, etc.
+pub(super) fn tokenize(i: &str) -> Result<Vec<LocatedToken>, ParseError> {
+    tokenize_lines(new_span(i))
 }
 
 #[cfg(test)]
@@ -222,12 +305,14 @@ mod tests {
     use super::*;
 
     fn test(input: &str) -> String {
-        let tokens = tokenize(input);
-        tokens
-            .iter()
-            .map(|t: &LocatedToken| format!("{}", t.token))
-            .collect::<Vec<_>>()
-            .join(" ")
+        match tokenize(input) {
+            Ok(tokens) => tokens
+                .iter()
+                .map(|t: &LocatedToken| format!("{}", t.token))
+                .collect::<Vec<_>>()
+                .join(" "),
+            Err(error) => error.to_string(),
+        }
     }
 
     #[test]
@@ -337,33 +422,105 @@ g
     }
 
     #[test]
-    fn illegal_outdent() {
+    fn continuation_lines() {
         k9::snapshot!(
-            tokenize_lines(new_span(
+            test(
                 "
-a
+x = (1 +
+     2)
+y
+"
+            ),
+            "x ␠ = ␠ ( 1 ␠ + ␠ 2 ) ␤ y ␤"
+        );
+        k9::snapshot!(
+            test(
+                "
+m = [
+  1 2
+
+  3 4
+]
+"
+            ),
+            "m ␠ = ␠ [ ␠ 1 ␠ 2 ␠ 3 ␠ 4 ␠ ] ␤"
+        );
+        // continuation lines don't indent or outdent anything
+        k9::snapshot!(
+            test(
+                "
+a =
+  b = (1
+2)
   b
- c
-d
+c
 "
-            )),
-            r#"
-Err(
-    Error(
-        Error {
-            input: LocatedSpan {
-                offset: 8,
-                line: 4,
-                fragment: "c
+            ),
+            "a ␠ = ␤ → b ␠ = ␠ ( 1 ␠ 2 ) ␤ b ␤ ← c ␤"
+        );
+        k9::snapshot!(test("(1\n-2)"), "( 1 ␠ -2 ) ␤");
+        k9::snapshot!(test("x = (1"), "x ␠ = ␠ ( 1 ␤");
+    }
+
+    #[test]
+    fn trailing_whitespace() {
+        k9::snapshot!(test("a =  \n  b \t\nc "), "a ␠ = ␤ → b ␤ ← c ␤");
+    }
+
+    #[test]
+    fn blank_lines() {
+        k9::snapshot!(
+            test(
+                "
+a =
+  b
+    \t
+  c
+ 
 d
-",
-                extra: (),
-            },
-            code: Fail,
-        },
-    ),
-)
-"#
+"
+            ),
+            "a ␠ = ␤ → b ␤ c ␤ ← d ␤"
+        );
+    }
+
+    #[test]
+    fn comments() {
+        k9::snapshot!(
+            test(
+                "
+# a comment
+a = # another comment
+  b # one more
+# not indented
+  c
+    # indented further
+d#e
+"
+            ),
+            "a ␠ = ␤ → b ␤ c ␤ ← d ␤"
         );
+        k9::snapshot!(test("x = (1 # one\n     2)"), "x ␠ = ␠ ( 1 ␠ 2 ) ␤");
+    }
+
+    #[test]
+    fn mixed_indentation() {
+        k9::snapshot!(
+            test("a =\n\tb\n  c"),
+            "indentation mixes tabs and spaces on line 3"
+        );
+    }
+
+    #[test]
+    fn mixed_indentation_within_a_line() {
+        k9::snapshot!(
+            test("a =\n \tb"),
+            "indentation mixes tabs and spaces on line 2"
+        );
+    }
+
+    #[test]
+    fn illegal_outdent() {
+        k9::snapshot!(test("a\n  b\n c\nd\n"), "invalid syntax on line 3");
     }
 }
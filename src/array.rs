@@ -1,4 +1,4 @@
-#[derive(Debug)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Array<T> {
     pub shape: Vec<usize>,
     pub data: Vec<T>,
@@ -109,3 +109,50 @@ impl<'a, T: std::fmt::Debug> ArrayView<'a, T> {
         format!("{:?}${:?}", self.shape, self.iter().collect::<Vec<_>>())
     }
 }
+
+impl<T> Array<T> {
+    pub fn scalar(value: T) -> Self {
+        Array {
+            shape: vec![],
+            data: vec![value],
+        }
+    }
+
+    pub fn rank(&self) -> usize {
+        self.shape.len()
+    }
+}
+
+// Formats arrays the way J does: vectors are separated by spaces, matrices are
+// printed row by row with aligned columns, and each higher dimension adds
+// another blank line between cells.
+impl<T: std::fmt::Display> std::fmt::Display for Array<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let atoms = self.data.iter().map(|x| x.to_string()).collect::<Vec<_>>();
+        match self.shape.len() {
+            0 | 1 => write!(f, "{}", atoms.join(" ")),
+            rank => {
+                let width = atoms.iter().map(String::len).max().unwrap_or(0);
+                let row_length = self.shape[rank - 1];
+                let row_count: usize = self.shape[..rank - 1].iter().product();
+                for row in 0..row_count {
+                    if row > 0 {
+                        let boundaries = (1..rank - 1)
+                            .filter(|axis| {
+                                row % self.shape[*axis..rank - 1].iter().product::<usize>() == 0
+                            })
+                            .count();
+                        write!(f, "{}", "\n".repeat(boundaries + 1))?;
+                    }
+                    let cells = &atoms[row * row_length..(row + 1) * row_length];
+                    let cells = cells
+                        .iter()
+                        .map(|cell| format!("{:>width$}", cell, width = width))
+                        .collect::<Vec<_>>();
+                    write!(f, "{}", cells.join(" "))?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
@@ -20,31 +20,24 @@ fn get_frame(shape: &[usize], rank: isize) -> &[usize] {
     &shape[0..actual_rank - cell_size]
 }
 
-fn reassemble(arrays: Vec<Array<i64>>, frame: &[usize]) -> Array<i64> {
+// Every result should have the given cell shape. We can't get it from the
+// results themselves, since there might not be any: a frame with a zero in it
+// has no cells at all, but the result still has to have the right shape.
+fn reassemble(arrays: Vec<Array<i64>>, frame: &[usize], cell_shape: &[usize]) -> Array<i64> {
     println!("reassembling {:?}\n  frame {:?}\n", arrays, frame);
-    if let Some(first_value) = arrays.first() {
-        let expected_shape = first_value.shape.clone();
-        let mut assembled_data: Vec<i64> =
-            Vec::with_capacity(expected_shape.iter().product::<usize>() * arrays.len());
-
-        for mut array in arrays {
-            if array.shape != expected_shape {
-                panic!("mismatched shapes while reassembling results")
-            }
-            assembled_data.append(&mut array.data);
-        }
-        let mut result_shape = frame.to_vec();
-        result_shape.extend(expected_shape);
-        Array {
-            shape: result_shape,
-            data: assembled_data,
-        }
-    } else {
-        // TODO: what does this case mean?
-        Array {
-            shape: frame.to_vec(),
-            data: vec![],
+    let mut assembled_data: Vec<i64> =
+        Vec::with_capacity(cell_shape.iter().product::<usize>() * arrays.len());
+    for mut array in arrays {
+        if array.shape != cell_shape {
+            panic!("mismatched shapes while reassembling results")
         }
+        assembled_data.append(&mut array.data);
+    }
+    let mut result_shape = frame.to_vec();
+    result_shape.extend(cell_shape);
+    Array {
+        shape: result_shape,
+        data: assembled_data,
     }
 }
 
@@ -93,6 +86,19 @@ pub fn ranked_add(
         common_frame, right_surplus_frame
     );
 
+    // adding cells of different ranks gives a result with the shape of the
+    // bigger one
+    let left_cell_shape = &left.shape[left_frame.len()..];
+    let right_cell_shape = &right.shape[right_frame.len()..];
+    let cell_shape = if left_cell_shape.len() >= right_cell_shape.len() {
+        left_cell_shape
+    } else {
+        right_cell_shape
+    }
+    .to_vec();
+    let mut macrocell_shape = surplus_frame.to_vec();
+    macrocell_shape.extend(&cell_shape);
+
     let left_macrocells = left.slice(common_frame.len());
     let right_macrocells = right.slice(common_frame.len());
 
@@ -132,8 +138,8 @@ pub fn ranked_add(
                     }
                 })
                 .collect::<Vec<_>>();
-            reassemble(results, surplus_frame)
+            reassemble(results, surplus_frame, &cell_shape)
         })
         .collect::<Vec<_>>();
-    reassemble(results, common_frame)
+    reassemble(results, common_frame, &macrocell_shape)
 }
@@ -0,0 +1,93 @@
+use petal_syntax::expression::Builtin;
+use petal_syntax::highlight::{highlight, Highlight};
+use petal_syntax::pos_parser::{Arity, ParseError, PartOfSpeech};
+
+// Renders the source with every highlighted span wrapped in the given
+// prefix and suffix, and everything else copied as-is.
+fn render(
+    source: &str,
+    escape: impl Fn(&str) -> String,
+    wrap: impl Fn(&Highlight, String) -> String,
+) -> Result<String, ParseError> {
+    let mut result = String::new();
+    let mut offset = 0;
+    for highlight in highlight(source)? {
+        // empty implicit builtins, like the composition in +-, have nothing to
+        // show
+        if highlight.span.is_empty() || highlight.span.start < offset {
+            continue;
+        }
+        result += &escape(&source[offset..highlight.span.start]);
+        let text = escape(&source[highlight.span.clone()]);
+        result += &wrap(&highlight, text);
+        offset = highlight.span.end;
+    }
+    result += &escape(&source[offset..]);
+    Ok(result)
+}
+
+fn ansi_color(pos: PartOfSpeech) -> u8 {
+    match pos {
+        PartOfSpeech::Noun => 36,
+        PartOfSpeech::Verb(Arity::Unary) => 32,
+        PartOfSpeech::Verb(Arity::Binary) => 33,
+        PartOfSpeech::Adverb(Arity::Unary, _, _) => 35,
+        PartOfSpeech::Adverb(Arity::Binary, _, _) => 31,
+    }
+}
+
+// Colors each name by its part of speech, and gives implicit builtins a gray
+// background.
+pub fn ansi(source: &str) -> Result<String, ParseError> {
+    render(source, str::to_string, |highlight, text| {
+        let mut codes = vec![];
+        if let Some(pos) = highlight.pos {
+            codes.push(ansi_color(pos).to_string());
+        }
+        if highlight.implicit.is_some() {
+            codes.push("100".to_string());
+        }
+        if codes.is_empty() {
+            return text;
+        }
+        format!("\x1b[{}m{}\x1b[0m", codes.join(";"), text)
+    })
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+fn implicit_class(builtin: Builtin) -> &'static str {
+    match builtin {
+        Builtin::Scale => "scale",
+        Builtin::PartialApplicationLeft => "partial-left",
+        Builtin::PartialApplicationRight => "partial-right",
+        Builtin::Compose => "compose",
+        Builtin::ComposeLeft => "compose-left",
+        Builtin::ComposeRight => "compose-right",
+    }
+}
+
+// Wraps each highlighted span in a span whose classes are its part of speech
+// (n, v1, v2, a1, or a2) and, for implicit builtins, "implicit" and the name
+// of the builtin. It's up to the page to style them.
+pub fn html(source: &str) -> Result<String, ParseError> {
+    let body = render(source, escape_html, |highlight, text| {
+        let mut classes = vec![];
+        if let Some(pos) = highlight.pos {
+            classes.push(pos.to_string());
+        }
+        if let Some(builtin) = highlight.implicit {
+            classes.push("implicit".to_string());
+            classes.push(implicit_class(builtin).to_string());
+        }
+        if classes.is_empty() {
+            return text;
+        }
+        format!("<span class=\"{}\">{}</span>", classes.join(" "), text)
+    })?;
+    Ok(format!("<pre class=\"petal\">{}</pre>\n", body))
+}
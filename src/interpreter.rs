@@ -0,0 +1,497 @@
+use super::array::Array;
+use super::primitive::{self, Primitive};
+use super::value::{Adverb, Closure, Value, Verb};
+use petal_syntax::expression::{Builtin, Expression, Identifier, RichIdentifier};
+use petal_syntax::pos_parser::{Arity, PartOfSpeech, Script};
+use petal_syntax::prelude;
+use std::{
+    cell::{Cell, RefCell},
+    collections::HashMap,
+    fmt,
+    rc::Rc,
+};
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub enum RuntimeError {
+    UnboundIdentifier(String),
+    CyclicDefinition(String),
+    InvalidLiteral(String),
+    Overflow,
+    DivisionByZero,
+    ShapeMismatch(Vec<usize>, Vec<usize>),
+    NotScalar(Vec<usize>),
+    NegativeCount(i64),
+    EmptyFold,
+    NotAnArray(String),
+    NotANoun(String),
+    // something that isn't a verb or an adverb, applied to something
+    NotApplicable(String),
+    IndexOutOfBounds(usize, usize),
+    LengthMismatch(usize, usize),
+    InvalidOperands(String),
+    IterationLimit(usize),
+    RecursionLimit(usize),
+}
+
+impl fmt::Display for RuntimeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use RuntimeError::*;
+        match self {
+            UnboundIdentifier(name) => write!(f, "{} is not defined", name),
+            CyclicDefinition(name) => write!(f, "{} is defined in terms of itself", name),
+            InvalidLiteral(num) => write!(f, "invalid numeric literal {}", num),
+            Overflow => write!(f, "integer overflow"),
+            DivisionByZero => write!(f, "division by zero"),
+            ShapeMismatch(x, y) => write!(f, "shape mismatch: {:?} and {:?}", x, y),
+            NotScalar(shape) => write!(f, "expected a single element, got shape {:?}", shape),
+            NegativeCount(count) => write!(f, "expected a non-negative count, got {}", count),
+            EmptyFold => write!(f, "cannot fold an empty array"),
+            NotAnArray(value) => write!(f, "{} is not an array", value),
+            NotANoun(value) => write!(f, "expected a noun, got {}", value),
+            NotApplicable(value) => write!(f, "cannot apply {}, which is a noun", value),
+            IndexOutOfBounds(index, length) => {
+                write!(f, "index {} is out of bounds for length {}", index, length)
+            }
+            LengthMismatch(expected, actual) => {
+                write!(f, "expected {} elements, got {}", expected, actual)
+            }
+            InvalidOperands(name) => write!(f, "invalid operands to {}", name),
+            IterationLimit(limit) => write!(f, "no fixed point after {} iterations", limit),
+            RecursionLimit(limit) => write!(f, "recursion deeper than {} calls", limit),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct Limits {
+    // how many times fix will apply a verb looking for a fixed point
+    pub max_iterations: usize,
+    // how many calls to user-defined verbs and adverbs can be in progress at
+    // once, so that runaway recursion is an error instead of a stack overflow
+    pub max_depth: usize,
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        Limits {
+            max_iterations: 10_000,
+            max_depth: 1_000,
+        }
+    }
+}
+
+// Assignments in a block are evaluated on demand, the first time something
+// refers to them, so that they can refer to names defined later in the block.
+enum Binding<'a> {
+    Unevaluated(&'a Expression),
+    Evaluating,
+    Evaluated(Value<'a>),
+}
+
+pub struct Scope<'a> {
+    bindings: RefCell<HashMap<Identifier, Binding<'a>>>,
+    parent: Option<Env<'a>>,
+}
+
+pub type Env<'a> = Rc<Scope<'a>>;
+
+impl<'a> Scope<'a> {
+    fn new(parent: Option<Env<'a>>) -> Env<'a> {
+        Rc::new(Scope {
+            bindings: RefCell::new(HashMap::new()),
+            parent,
+        })
+    }
+
+    fn bind(&self, id: &RichIdentifier, value: Value<'a>) {
+        self.bindings
+            .borrow_mut()
+            .insert(id.id, Binding::Evaluated(value));
+    }
+
+    fn bind_lazily(&self, id: &RichIdentifier, expr: &'a Expression) {
+        self.bindings
+            .borrow_mut()
+            .insert(id.id, Binding::Unevaluated(expr));
+    }
+
+    // Returns the scope that binds the identifier. Identifiers are unique, so
+    // there's no need to worry about shadowing here: the parser already
+    // decided which definition every reference points to.
+    fn owner(env: &Env<'a>, id: &RichIdentifier) -> Option<Env<'a>> {
+        let mut scope = env;
+        loop {
+            if scope.bindings.borrow().contains_key(&id.id) {
+                return Some(scope.clone());
+            }
+            scope = scope.parent.as_ref()?;
+        }
+    }
+}
+
+fn lookup_prelude<'a>(id: &RichIdentifier) -> Option<Value<'a>> {
+    let primitive = Primitive::of_name(prelude::name_of(id.id)?)?;
+    Some(match primitive.part_of_speech() {
+        PartOfSpeech::Verb(_) => Value::Verb(Rc::new(Verb::Primitive(primitive))),
+        _ => Value::Adverb(Rc::new(Adverb::Primitive(primitive))),
+    })
+}
+
+pub struct Interpreter {
+    limits: Limits,
+    depth: Cell<usize>,
+}
+
+impl Interpreter {
+    pub fn new(limits: Limits) -> Self {
+        Interpreter {
+            limits,
+            depth: Cell::new(0),
+        }
+    }
+
+    pub fn run<'a>(&self, expr: &'a Expression) -> Result<Value<'a>, RuntimeError> {
+        self.eval(expr, &Scope::new(None))
+    }
+
+    // Evaluates every statement of a script in order, handing over each result
+    // as soon as it's known, so that the results before an error still show
+    // up.
+    pub fn run_script<'a>(
+        &self,
+        script: &'a Script,
+        mut each_result: impl FnMut(Value<'a>),
+    ) -> Result<(), RuntimeError> {
+        let scope = Scope::new(None);
+        for (rich_id, expr) in &script.assignments {
+            scope.bind_lazily(rich_id, expr);
+        }
+        let mut ids = script.assignments.keys().collect::<Vec<_>>();
+        ids.sort_by_key(|rich_id| rich_id.id);
+        for rich_id in ids {
+            let value = self.force(&scope, rich_id)?;
+            if script.results.contains(rich_id) {
+                each_result(value);
+            }
+        }
+        Ok(())
+    }
+
+    fn eval<'a>(&self, expr: &'a Expression, env: &Env<'a>) -> Result<Value<'a>, RuntimeError> {
+        use Expression::*;
+        match expr {
+            Compound(assignments, result) => {
+                let scope = Scope::new(Some(env.clone()));
+                for (rich_id, expr) in assignments {
+                    scope.bind_lazily(rich_id, expr);
+                }
+                // Everything is evaluated, even if it's never referenced, so
+                // that errors don't depend on whether a name is used.
+                let mut ids = assignments.keys().collect::<Vec<_>>();
+                ids.sort_by_key(|rich_id| rich_id.id);
+                for rich_id in ids {
+                    self.force(&scope, rich_id)?;
+                }
+                self.eval(result, &scope)
+            }
+            Lambda(params, body, pos) => {
+                let closure = Closure {
+                    params,
+                    body,
+                    pos: *pos,
+                    env: env.clone(),
+                };
+                Ok(match pos {
+                    PartOfSpeech::Verb(_) => Value::Verb(Rc::new(Verb::Closure(closure))),
+                    _ => Value::Adverb(Rc::new(Adverb::Closure(closure))),
+                })
+            }
+            Conditional(condition, consequent, alternative) => {
+                let condition = self.eval(condition, env)?;
+                if primitive::only_atom(condition.as_noun()?)? != 0 {
+                    self.eval(consequent, env)
+                } else {
+                    self.eval(alternative, env)
+                }
+            }
+            Implicit(builtin) => Ok(Value::Adverb(Rc::new(Adverb::Implicit(*builtin)))),
+            Identifier(rich_id) => match Scope::owner(env, rich_id) {
+                Some(scope) => self.force(&scope, rich_id),
+                None => lookup_prelude(rich_id)
+                    .ok_or_else(|| RuntimeError::UnboundIdentifier(rich_id.name.clone())),
+            },
+            NumericLiteral(num) => num
+                .parse()
+                .map(|num| Value::noun(Array::scalar(num)))
+                .map_err(|_| RuntimeError::InvalidLiteral(num.clone())),
+            Parens(expr) => self.eval(expr, env),
+            Tuple(exprs) => {
+                let elements = exprs
+                    .iter()
+                    .map(|expr| self.eval(expr, env))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Value::Tuple(Rc::new(elements)))
+            }
+            Element(tuple, index, length) => {
+                let elements = self.eval(tuple, env)?.elements()?;
+                if elements.len() != *length {
+                    return Err(RuntimeError::LengthMismatch(*length, elements.len()));
+                }
+                Ok(elements[*index].clone())
+            }
+            Brackets(exprs) => {
+                let items = exprs
+                    .iter()
+                    .map(|expr| Ok(self.eval(expr, env)?.as_noun()?.as_ref().clone()))
+                    .collect::<Result<Vec<_>, _>>()?;
+                Ok(Value::noun(primitive::from_items(items)?))
+            }
+            UnaryApplication(func, arg) => {
+                let func = self.eval(func, env)?;
+                let arg = self.eval(arg, env)?;
+                match func {
+                    Value::Verb(verb) => self.apply_unary(&verb, arg),
+                    Value::Adverb(adverb) => self.derive(&adverb, vec![arg]),
+                    Value::Noun(_) | Value::Tuple(_) => {
+                        Err(RuntimeError::NotApplicable(func.to_string()))
+                    }
+                }
+            }
+            BinaryApplication(func, lhs, rhs) => {
+                let func = self.eval(func, env)?;
+                let lhs = self.eval(lhs, env)?;
+                let rhs = self.eval(rhs, env)?;
+                match func {
+                    Value::Verb(verb) => self.apply_binary(&verb, lhs, rhs),
+                    Value::Adverb(adverb) => self.derive(&adverb, vec![lhs, rhs]),
+                    Value::Noun(_) | Value::Tuple(_) => {
+                        Err(RuntimeError::NotApplicable(func.to_string()))
+                    }
+                }
+            }
+        }
+    }
+
+    fn force<'a>(&self, scope: &Env<'a>, id: &RichIdentifier) -> Result<Value<'a>, RuntimeError> {
+        let binding = scope
+            .bindings
+            .borrow_mut()
+            .insert(id.id, Binding::Evaluating)
+            .unwrap();
+        let expr = match binding {
+            Binding::Evaluated(value) => {
+                scope.bind(id, value.clone());
+                return Ok(value);
+            }
+            Binding::Evaluating => {
+                return Err(RuntimeError::CyclicDefinition(id.name.clone()));
+            }
+            Binding::Unevaluated(expr) => expr,
+        };
+        match self.eval(expr, scope) {
+            Ok(value) => {
+                scope.bind(id, value.clone());
+                Ok(value)
+            }
+            Err(error) => {
+                scope.bind_lazily(id, expr);
+                Err(error)
+            }
+        }
+    }
+
+    // Binds the parameters of a closure to its arguments and evaluates its body.
+    fn call<'a>(
+        &self,
+        closure: &Closure<'a>,
+        args: Vec<Value<'a>>,
+    ) -> Result<Value<'a>, RuntimeError> {
+        let depth = self.depth.get();
+        if depth >= self.limits.max_depth {
+            return Err(RuntimeError::RecursionLimit(self.limits.max_depth));
+        }
+        let scope = Scope::new(Some(closure.env.clone()));
+        for ((param, _), arg) in closure.params.iter().zip(args) {
+            scope.bind(param, arg);
+        }
+        self.depth.set(depth + 1);
+        let result = self.eval(closure.body, &scope);
+        self.depth.set(depth);
+        result
+    }
+
+    fn derive<'a>(
+        &self,
+        adverb: &Rc<Adverb<'a>>,
+        operands: Vec<Value<'a>>,
+    ) -> Result<Value<'a>, RuntimeError> {
+        match adverb.as_ref() {
+            Adverb::Closure(closure) => self.call(closure, operands),
+            _ => Ok(Value::Verb(Rc::new(Verb::Derived(
+                adverb.clone(),
+                operands,
+            )))),
+        }
+    }
+
+    // Verbs take and return values, not arrays, so that user-defined verbs can
+    // work with tuples. Most primitives only know what to do with arrays,
+    // though.
+    fn apply_unary<'a>(&self, verb: &Verb<'a>, x: Value<'a>) -> Result<Value<'a>, RuntimeError> {
+        use Primitive::*;
+        match verb {
+            Verb::Primitive(First) => element(&x, 0),
+            Verb::Primitive(Second) => element(&x, 1),
+            Verb::Primitive(Length) => {
+                let length = x.elements()?.len() as i64;
+                Ok(Value::noun(Array::scalar(length)))
+            }
+            Verb::Primitive(primitive) => Ok(Value::noun(primitive::apply_unary(
+                *primitive,
+                x.as_noun()?,
+            )?)),
+            Verb::Closure(closure) => self.call(closure, vec![x]),
+            Verb::Derived(adverb, operands) => self.apply_derived_unary(adverb, operands, x),
+        }
+    }
+
+    fn apply_binary<'a>(
+        &self,
+        verb: &Verb<'a>,
+        x: Value<'a>,
+        y: Value<'a>,
+    ) -> Result<Value<'a>, RuntimeError> {
+        match verb {
+            Verb::Primitive(primitive) => Ok(Value::noun(primitive::apply_binary(
+                *primitive,
+                x.as_noun()?,
+                y.as_noun()?,
+            )?)),
+            Verb::Closure(closure) => self.call(closure, vec![x, y]),
+            Verb::Derived(adverb, operands) => self.apply_derived_binary(adverb, operands, x, y),
+        }
+    }
+
+    fn apply_derived_unary<'a>(
+        &self,
+        adverb: &Adverb<'a>,
+        operands: &[Value<'a>],
+        x: Value<'a>,
+    ) -> Result<Value<'a>, RuntimeError> {
+        use Arity::*;
+        use Builtin::*;
+        use Primitive::*;
+        match (adverb, operands) {
+            (Adverb::Implicit(Scale), [n]) => {
+                let coefficient = primitive::only_atom(n.as_noun()?)?;
+                Ok(Value::noun(primitive::scale(coefficient, x.as_noun()?)?))
+            }
+            (Adverb::Implicit(PartialApplicationLeft), [Value::Verb(f), n]) => {
+                self.apply_binary(f, n.clone(), x)
+            }
+            (Adverb::Implicit(PartialApplicationRight), [Value::Verb(f), n]) => {
+                self.apply_binary(f, x, n.clone())
+            }
+            (Adverb::Implicit(Compose), [Value::Verb(f), Value::Verb(g)]) => {
+                self.apply_unary(f, self.apply_unary(g, x)?)
+            }
+            (Adverb::Primitive(Fold), [Value::Verb(f)]) if f.arity() == Binary => {
+                let mut items = x.elements()?;
+                let mut acc = match items.pop() {
+                    Some(item) => item,
+                    None => return self.empty_fold(f, &x),
+                };
+                for item in items.into_iter().rev() {
+                    acc = self.apply_binary(f, item, acc)?;
+                }
+                Ok(acc)
+            }
+            (Adverb::Primitive(Reflex), [Value::Verb(f)]) if f.arity() == Binary => {
+                self.apply_binary(f, x.clone(), x)
+            }
+            (Adverb::Primitive(Fix), [Value::Verb(f)]) if f.arity() == Unary => {
+                let mut x = x.as_noun()?.clone();
+                for _ in 0..self.limits.max_iterations {
+                    let next = self.apply_unary(f, Value::Noun(x.clone()))?;
+                    let next = next.as_noun()?;
+                    if *next == x {
+                        return Ok(Value::Noun(x));
+                    }
+                    x = next.clone();
+                }
+                Err(RuntimeError::IterationLimit(self.limits.max_iterations))
+            }
+            (Adverb::Primitive(Power), [Value::Verb(f), n]) if f.arity() == Unary => {
+                let mut x = x;
+                for _ in 0..primitive::count(n.as_noun()?)? {
+                    x = self.apply_unary(f, x)?;
+                }
+                Ok(x)
+            }
+            (adverb, _) => Err(RuntimeError::InvalidOperands(adverb.to_string())),
+        }
+    }
+
+    // Folding zero items gives the identity of the verb, shaped like one of the
+    // items that would have been there. The elements of a tuple have no shape
+    // in common, so folding the empty tuple gives a scalar. Only some
+    // primitives have one.
+    fn empty_fold<'a>(&self, f: &Verb, x: &Value<'a>) -> Result<Value<'a>, RuntimeError> {
+        let identity = match f {
+            Verb::Primitive(primitive) => primitive::identity(*primitive),
+            _ => None,
+        };
+        let identity = identity.ok_or(RuntimeError::EmptyFold)?;
+        let item_shape = match x {
+            Value::Tuple(_) => vec![],
+            _ => x.as_noun()?.shape[1..].to_vec(),
+        };
+        Ok(Value::noun(Array {
+            data: vec![identity; item_shape.iter().product()],
+            shape: item_shape,
+        }))
+    }
+
+    fn apply_derived_binary<'a>(
+        &self,
+        adverb: &Adverb<'a>,
+        operands: &[Value<'a>],
+        x: Value<'a>,
+        y: Value<'a>,
+    ) -> Result<Value<'a>, RuntimeError> {
+        use Arity::*;
+        use Builtin::*;
+        use Primitive::*;
+        match (adverb, operands) {
+            (Adverb::Implicit(ComposeLeft), [Value::Verb(f), Value::Verb(g)]) => {
+                self.apply_binary(f, self.apply_unary(g, x)?, y)
+            }
+            (Adverb::Implicit(ComposeRight), [Value::Verb(f), Value::Verb(g)]) => {
+                self.apply_binary(f, x, self.apply_unary(g, y)?)
+            }
+            (Adverb::Primitive(Flip), [Value::Verb(f)]) if f.arity() == Binary => {
+                self.apply_binary(f, y, x)
+            }
+            (Adverb::Primitive(Dot), [Value::Verb(f), Value::Verb(g)]) => {
+                match (f.arity(), g.arity()) {
+                    (Binary, Unary) => {
+                        self.apply_binary(f, self.apply_unary(g, x)?, self.apply_unary(g, y)?)
+                    }
+                    (Unary, Binary) => self.apply_unary(f, self.apply_binary(g, x, y)?),
+                    _ => Err(RuntimeError::InvalidOperands(Dot.name().to_string())),
+                }
+            }
+            (adverb, _) => Err(RuntimeError::InvalidOperands(adverb.to_string())),
+        }
+    }
+}
+
+// An element of a tuple, or an item of an array.
+fn element<'a>(x: &Value<'a>, index: usize) -> Result<Value<'a>, RuntimeError> {
+    let elements = x.elements()?;
+    let length = elements.len();
+    elements
+        .into_iter()
+        .nth(index)
+        .ok_or(RuntimeError::IndexOutOfBounds(index, length))
+}
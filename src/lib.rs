@@ -1,2 +1,7 @@
 pub mod array;
 pub mod eval;
+pub mod highlight;
+pub mod interpreter;
+pub mod lsp;
+pub mod primitive;
+pub mod value;
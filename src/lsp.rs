@@ -0,0 +1,364 @@
+use lsp_server::{Connection, ErrorCode, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as NotificationTrait, PublishDiagnostics,
+};
+use lsp_types::request::{GotoDefinition, HoverRequest, References, Request as RequestTrait};
+use lsp_types::{
+    Diagnostic, DiagnosticSeverity, GotoDefinitionResponse, Hover, HoverContents,
+    HoverProviderCapability, Location, MarkedString, OneOf, Position, PublishDiagnosticsParams,
+    Range, ServerCapabilities, TextDocumentContentChangeEvent, TextDocumentSyncCapability,
+    TextDocumentSyncKind, Url,
+};
+use petal_syntax::pos_parser::{Failure, ParseError, Symbol, Symbols};
+use petal_syntax::session::Session;
+use std::{collections::HashMap, error::Error};
+
+// A parsed version of an open file, which is reparsed incrementally as it
+// changes.
+pub struct Document {
+    session: Session,
+    text: String,
+    symbols: Symbols,
+    diagnostics: Vec<Diagnostic>,
+}
+
+// Positions in the protocol are lines and UTF-16 code units, so converting
+// between them and byte offsets means scanning the text.
+pub fn position_of_offset(text: &str, offset: usize) -> Position {
+    let before = &text[..offset];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    Position {
+        line: before.matches('\n').count() as u32,
+        character: before[line_start..].encode_utf16().count() as u32,
+    }
+}
+
+// Positions past the end of a line are clamped to the end of the line.
+pub fn offset_of_position(text: &str, position: Position) -> usize {
+    let mut line_start = 0;
+    for _ in 0..position.line {
+        match text[line_start..].find('\n') {
+            Some(i) => line_start += i + 1,
+            None => return text.len(),
+        }
+    }
+    let line = text[line_start..].split('\n').next().unwrap();
+    let mut units = 0;
+    for (i, c) in line.char_indices() {
+        if units >= position.character as usize {
+            return line_start + i;
+        }
+        units += c.len_utf16();
+    }
+    line_start + line.len()
+}
+
+impl Document {
+    pub fn new(text: String) -> Self {
+        let mut document = Document {
+            session: Session::new(&text),
+            text,
+            symbols: Symbols::default(),
+            diagnostics: vec![],
+        };
+        document.reparse();
+        document
+    }
+
+    // Applies the changes in order, each to the text left by the one before,
+    // and then parses whatever they touched. A range that ends before it
+    // starts is taken to mean the same text as it would the right way around.
+    pub fn edit(&mut self, changes: Vec<TextDocumentContentChangeEvent>) {
+        for change in changes {
+            let range = match change.range {
+                Some(range) => {
+                    let start = offset_of_position(&self.text, range.start);
+                    let end = offset_of_position(&self.text, range.end);
+                    start.min(end)..start.max(end)
+                }
+                None => 0..self.text.len(),
+            };
+            self.session.edit(range.clone(), &change.text);
+            self.text.replace_range(range, &change.text);
+        }
+        self.reparse();
+    }
+
+    fn reparse(&mut self) {
+        let (result, symbols) = self.session.parse_with_symbols();
+        self.diagnostics = match result {
+            Ok(_) => vec![],
+            Err(error) => error
+                .root_causes()
+                .iter()
+                .map(|failure| diagnostic(&self.text, failure))
+                .collect(),
+        };
+        self.symbols = symbols;
+    }
+
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    fn range(&self, symbol: &Symbol) -> Range {
+        span(&self.text, symbol.location.offset, &symbol.name)
+    }
+
+    fn symbols(&self) -> impl Iterator<Item = &Symbol> {
+        self.symbols
+            .definitions
+            .iter()
+            .chain(&self.symbols.references)
+    }
+
+    // The definition or reference under the cursor, if any. A cursor just
+    // after a name still counts as being on it.
+    fn symbol_at(&self, position: Position) -> Option<&Symbol> {
+        let offset = offset_of_position(&self.text, position);
+        self.symbols().find(|symbol| {
+            let start = symbol.location.offset;
+            start <= offset && offset <= start + symbol.name.len()
+        })
+    }
+
+    pub fn hover(&self, position: Position) -> Option<Hover> {
+        let symbol = self.symbol_at(position)?;
+        let pos = symbol.pos?;
+        Some(Hover {
+            contents: HoverContents::Scalar(MarkedString::String(format!(
+                "{} : {}",
+                symbol.name, pos
+            ))),
+            range: Some(self.range(symbol)),
+        })
+    }
+
+    pub fn definition(&self, position: Position) -> Option<Range> {
+        let id = self.symbol_at(position)?.id;
+        self.symbols
+            .definitions
+            .iter()
+            .find(|symbol| symbol.id == id)
+            .map(|symbol| self.range(symbol))
+    }
+
+    pub fn references(&self, position: Position, include_declaration: bool) -> Vec<Range> {
+        let id = match self.symbol_at(position) {
+            Some(symbol) => symbol.id,
+            None => return vec![],
+        };
+        let definitions = self
+            .symbols
+            .definitions
+            .iter()
+            .filter(|_| include_declaration);
+        definitions
+            .chain(&self.symbols.references)
+            .filter(|symbol| symbol.id == id)
+            .map(|symbol| self.range(symbol))
+            .collect()
+    }
+}
+
+fn span(text: &str, offset: usize, name: &str) -> Range {
+    Range {
+        start: position_of_offset(text, offset),
+        end: position_of_offset(text, offset + name.len()),
+    }
+}
+
+// Errors are reported on the name or operator that we didn't recognize, on the
+// rest of the line where the tokenizer or statement parser gave up, or else on
+// the innermost assignment that they happened inside of.
+fn diagnostic(text: &str, failure: &Failure) -> Diagnostic {
+    let range = match failure.error {
+        ParseError::UnboundName(name, location, _)
+        | ParseError::UnknownOperator(name, location, _)
+        | ParseError::BoundTwice(name, location) => span(text, location.offset, name),
+        ParseError::InvalidSyntax(location) | ParseError::MixedIndentation(location) => {
+            let line = text[location.offset..].split('\n').next().unwrap();
+            span(text, location.offset, line)
+        }
+        _ => failure
+            .bindings
+            .iter()
+            .rev()
+            .find_map(|binding| Some(span(text, binding.location?.offset, &binding.name)))
+            .unwrap_or_default(),
+    };
+    Diagnostic {
+        range,
+        severity: Some(DiagnosticSeverity::ERROR),
+        source: Some("petal".to_string()),
+        message: failure.error.to_string(),
+        ..Diagnostic::default()
+    }
+}
+
+type Result<T> = std::result::Result<T, Box<dyn Error + Sync + Send>>;
+
+struct Server {
+    connection: Connection,
+    documents: HashMap<Url, Document>,
+}
+
+impl Server {
+    fn publish_diagnostics(&self, uri: Url) -> Result<()> {
+        let diagnostics = match self.documents.get(&uri) {
+            Some(document) => document.diagnostics().to_vec(),
+            None => return Ok(()),
+        };
+        let params = PublishDiagnosticsParams {
+            uri,
+            diagnostics,
+            version: None,
+        };
+        self.notify::<PublishDiagnostics>(params)
+    }
+
+    fn notify<N: NotificationTrait>(&self, params: N::Params) -> Result<()> {
+        let notification = Notification::new(N::METHOD.to_string(), params);
+        self.connection
+            .sender
+            .send(Message::Notification(notification))?;
+        Ok(())
+    }
+
+    // Malformed notifications are ignored, since there's no one to tell about
+    // them.
+    fn handle_notification(&mut self, notification: Notification) -> Result<()> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                if let Some(params) = params::<DidOpenTextDocument>(notification) {
+                    let document = params.text_document;
+                    self.documents
+                        .insert(document.uri.clone(), Document::new(document.text));
+                    return self.publish_diagnostics(document.uri);
+                }
+            }
+            DidChangeTextDocument::METHOD => {
+                if let Some(params) = params::<DidChangeTextDocument>(notification) {
+                    let uri = params.text_document.uri;
+                    if let Some(document) = self.documents.get_mut(&uri) {
+                        document.edit(params.content_changes);
+                        return self.publish_diagnostics(uri);
+                    }
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                if let Some(params) = params::<DidCloseTextDocument>(notification) {
+                    self.documents.remove(&params.text_document.uri);
+                }
+            }
+            _ => (),
+        }
+        Ok(())
+    }
+
+    fn handle_request(&mut self, request: Request) -> Result<()> {
+        match request.method.as_str() {
+            HoverRequest::METHOD => self.respond::<HoverRequest>(request, |documents, params| {
+                let params = params.text_document_position_params;
+                documents
+                    .get(&params.text_document.uri)
+                    .and_then(|document| document.hover(params.position))
+            }),
+            GotoDefinition::METHOD => {
+                self.respond::<GotoDefinition>(request, |documents, params| {
+                    let params = params.text_document_position_params;
+                    let uri = params.text_document.uri;
+                    documents
+                        .get(&uri)
+                        .and_then(|document| document.definition(params.position))
+                        .map(|range| GotoDefinitionResponse::Scalar(Location::new(uri, range)))
+                })
+            }
+            References::METHOD => self.respond::<References>(request, |documents, params| {
+                let position = params.text_document_position;
+                let uri = position.text_document.uri;
+                documents.get(&uri).map(|document| {
+                    document
+                        .references(position.position, params.context.include_declaration)
+                        .into_iter()
+                        .map(|range| Location::new(uri.clone(), range))
+                        .collect()
+                })
+            }),
+            _ => {
+                let response = Response::new_err(
+                    request.id,
+                    ErrorCode::MethodNotFound as i32,
+                    format!("unknown method {}", request.method),
+                );
+                self.connection.sender.send(Message::Response(response))?;
+                Ok(())
+            }
+        }
+    }
+
+    // Malformed parameters fail the request that they came with, rather than
+    // the whole server.
+    fn respond<R: RequestTrait>(
+        &self,
+        request: Request,
+        handle: impl FnOnce(&HashMap<Url, Document>, R::Params) -> R::Result,
+    ) -> Result<()> {
+        let response = match serde_json::from_value(request.params) {
+            Ok(params) => Response::new_ok(request.id, handle(&self.documents, params)),
+            Err(error) => Response::new_err(
+                request.id,
+                ErrorCode::InvalidParams as i32,
+                error.to_string(),
+            ),
+        };
+        self.connection.sender.send(Message::Response(response))?;
+        Ok(())
+    }
+}
+
+fn params<N: NotificationTrait>(notification: Notification) -> Option<N::Params> {
+    serde_json::from_value(notification.params).ok()
+}
+
+// Runs a language server over stdin and stdout until the client shuts it down.
+pub fn run() -> Result<()> {
+    let (connection, io_threads) = Connection::stdio();
+    serve(connection)?;
+    io_threads.join()?;
+    Ok(())
+}
+
+// Runs a language server over any connection, starting with the
+// initialization handshake.
+pub fn serve(connection: Connection) -> Result<()> {
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(
+            TextDocumentSyncKind::INCREMENTAL,
+        )),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        references_provider: Some(OneOf::Left(true)),
+        ..ServerCapabilities::default()
+    };
+    connection.initialize(serde_json::to_value(capabilities)?)?;
+
+    let mut server = Server {
+        connection,
+        documents: HashMap::new(),
+    };
+    while let Ok(message) = server.connection.receiver.recv() {
+        match message {
+            Message::Request(request) => {
+                if server.connection.handle_shutdown(&request)? {
+                    break;
+                }
+                server.handle_request(request)?;
+            }
+            Message::Notification(notification) => server.handle_notification(notification)?,
+            Message::Response(_) => (),
+        }
+    }
+    Ok(())
+}
@@ -1,4 +1,6 @@
-use std::{fs, path::PathBuf};
+use petal::interpreter::{Interpreter, Limits};
+use petal_syntax::dump::{Stage, STAGES};
+use std::{fmt::Display, fs, path::PathBuf, process, thread};
 use structopt::StructOpt;
 
 #[derive(StructOpt)]
@@ -13,17 +15,141 @@ enum Command {
     Eval {
         expression: String,
     },
+    Lsp,
+    Fmt {
+        #[structopt(parse(from_os_str))]
+        filenames: Vec<PathBuf>,
+        // don't write anything, just fail if any file isn't formatted
+        #[structopt(long)]
+        check: bool,
+    },
+    Parse {
+        #[structopt(parse(from_os_str))]
+        filename: PathBuf,
+        // which stage of the parser to show the output of
+        #[structopt(long, possible_values = STAGES)]
+        dump: Option<Stage>,
+        // print the expression as JSON
+        #[structopt(long, conflicts_with = "dump")]
+        json: bool,
+    },
+    Highlight {
+        #[structopt(parse(from_os_str))]
+        filename: PathBuf,
+        #[structopt(long)]
+        html: bool,
+    },
+}
+
+// Every call to a user-defined verb uses a few kilobytes of native stack, so
+// the interpreter gets a thread with plenty of room for Limits::max_depth.
+const STACK_SIZE: usize = 256 << 20;
+
+// Prints every top-level result, in order.
+fn run(source: &str) {
+    let script = match petal_syntax::parse_script(source) {
+        Ok(script) => script,
+        Err(error) => fail(error),
+    };
+    let result = thread::scope(|scope| {
+        thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, || {
+                Interpreter::new(Limits::default())
+                    .run_script(&script, |value| println!("{}", value))
+            })
+            .unwrap()
+            .join()
+            .unwrap()
+    });
+    if let Err(error) = result {
+        fail(error)
+    }
+}
+
+fn format(filenames: Vec<PathBuf>, check: bool) {
+    let mut unformatted = false;
+    for filename in filenames {
+        let contents = fs::read_to_string(&filename).expect("unable to read file");
+        let formatted = match petal_syntax::formatter::format(&contents) {
+            Ok(formatted) => formatted,
+            Err(error) => fail(format!("{}: {}", filename.display(), error)),
+        };
+        if !petal_syntax::formatter::same_meaning(&contents, &formatted) {
+            fail(format!(
+                "formatting {} would change its meaning",
+                filename.display()
+            ));
+        }
+        if formatted == contents {
+            continue;
+        }
+        if check {
+            println!("{}", filename.display());
+            unformatted = true;
+        } else {
+            fs::write(&filename, formatted).expect("unable to write file");
+        }
+    }
+    if unformatted {
+        process::exit(1);
+    }
+}
+
+fn fail(error: impl Display) -> ! {
+    eprintln!("error: {}", error);
+    process::exit(1)
 }
 
 fn main() {
     match Command::from_args() {
         Command::Run { filenames, inline } => {
-            println!("{:?} {}", filenames, inline);
             for filename in filenames {
-                let contents = fs::read_to_string(filename).expect("unable to read file");
-                petal_syntax::parse(&contents).unwrap();
+                // with -i the arguments are programs rather than paths
+                let contents = if inline {
+                    filename.to_string_lossy().into_owned()
+                } else {
+                    fs::read_to_string(filename).expect("unable to read file")
+                };
+                run(&contents);
+            }
+        }
+        Command::Eval { expression } => run(&expression),
+        Command::Fmt { filenames, check } => format(filenames, check),
+        Command::Parse {
+            filename,
+            dump,
+            json,
+        } => {
+            let contents = fs::read_to_string(filename).expect("unable to read file");
+            if json {
+                match petal_syntax::json::parse_to_json(&contents) {
+                    Ok(value) => println!("{}", value),
+                    Err(error) => fail(error),
+                }
+                return;
+            }
+            match petal_syntax::dump::dump(&contents, dump.unwrap_or(Stage::Expression)) {
+                Ok(output) => print!("{}", output),
+                Err(error) => fail(error),
+            }
+        }
+        Command::Highlight { filename, html } => {
+            let contents = fs::read_to_string(filename).expect("unable to read file");
+            let highlighted = if html {
+                petal::highlight::html(&contents)
+            } else {
+                petal::highlight::ansi(&contents)
+            };
+            match highlighted {
+                Ok(output) => print!("{}", output),
+                Err(error) => fail(error),
+            }
+        }
+        Command::Lsp => {
+            if let Err(error) = petal::lsp::run() {
+                fail(error)
             }
         }
-        Command::Eval { expression } => println!("{}", expression),
     }
 }
@@ -0,0 +1,274 @@
+use super::array::Array;
+use super::interpreter::RuntimeError;
+use petal_syntax::pos_parser::PartOfSpeech;
+use petal_syntax::prelude::PRELUDE;
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub enum Primitive {
+    Add,
+    Subtract,
+    Multiply,
+    Divide,
+    Modulo,
+    Max,
+    Min,
+    Equal,
+    NotEqual,
+    Less,
+    Greater,
+    LessOrEqual,
+    GreaterOrEqual,
+    Negate,
+    Sign,
+    Abs,
+    Iota,
+    First,
+    Second,
+    Length,
+    Fold,
+    Flip,
+    Reflex,
+    Fix,
+    Dot,
+    Power,
+}
+use Primitive::*;
+
+const NAMES: &[(&str, Primitive)] = &[
+    ("+", Add),
+    ("-", Subtract),
+    ("*", Multiply),
+    ("/", Divide),
+    ("%", Modulo),
+    ("max", Max),
+    ("min", Min),
+    ("==", Equal),
+    ("!=", NotEqual),
+    ("<", Less),
+    (">", Greater),
+    ("<=", LessOrEqual),
+    (">=", GreaterOrEqual),
+    ("neg", Negate),
+    ("sign", Sign),
+    ("abs", Abs),
+    ("iota", Iota),
+    ("first", First),
+    ("second", Second),
+    ("length", Length),
+    ("fold", Fold),
+    ("flip", Flip),
+    ("~", Reflex),
+    ("fix", Fix),
+    (".", Dot),
+    ("power", Power),
+];
+
+impl Primitive {
+    pub fn of_name(name: &str) -> Option<Self> {
+        NAMES
+            .iter()
+            .find(|(primitive_name, _)| *primitive_name == name)
+            .map(|(_, primitive)| *primitive)
+    }
+
+    pub fn name(self) -> &'static str {
+        NAMES
+            .iter()
+            .find(|(_, primitive)| *primitive == self)
+            .map(|(name, _)| *name)
+            .unwrap()
+    }
+
+    // the parser is the source of truth for the part of speech of every
+    // primitive
+    pub fn part_of_speech(self) -> PartOfSpeech {
+        let name = self.name();
+        PRELUDE
+            .iter()
+            .find(|(prelude_name, _)| *prelude_name == name)
+            .map(|(_, pos)| *pos)
+            .unwrap()
+    }
+}
+
+fn floor_divide(x: i64, y: i64) -> Result<i64, RuntimeError> {
+    if y == 0 {
+        return Err(RuntimeError::DivisionByZero);
+    }
+    let quotient = x.checked_div(y).ok_or(RuntimeError::Overflow)?;
+    if x % y != 0 && ((x < 0) != (y < 0)) {
+        Ok(quotient - 1)
+    } else {
+        Ok(quotient)
+    }
+}
+
+fn floor_modulo(x: i64, y: i64) -> Result<i64, RuntimeError> {
+    if y == 0 {
+        return Err(RuntimeError::DivisionByZero);
+    }
+    let remainder = x.checked_rem(y).ok_or(RuntimeError::Overflow)?;
+    if remainder != 0 && ((remainder < 0) != (y < 0)) {
+        Ok(remainder + y)
+    } else {
+        Ok(remainder)
+    }
+}
+
+type ScalarOp = fn(i64, i64) -> Result<i64, RuntimeError>;
+
+fn scalar_binary_op(primitive: Primitive) -> Option<ScalarOp> {
+    fn checked(result: Option<i64>) -> Result<i64, RuntimeError> {
+        result.ok_or(RuntimeError::Overflow)
+    }
+    Some(match primitive {
+        Add => |x: i64, y| checked(x.checked_add(y)),
+        Subtract => |x: i64, y| checked(x.checked_sub(y)),
+        Multiply => |x: i64, y| checked(x.checked_mul(y)),
+        Divide => floor_divide,
+        Modulo => floor_modulo,
+        Max => |x: i64, y| Ok(x.max(y)),
+        Min => |x: i64, y| Ok(x.min(y)),
+        Equal => |x, y| Ok((x == y) as i64),
+        NotEqual => |x, y| Ok((x != y) as i64),
+        Less => |x, y| Ok((x < y) as i64),
+        Greater => |x, y| Ok((x > y) as i64),
+        LessOrEqual => |x, y| Ok((x <= y) as i64),
+        GreaterOrEqual => |x, y| Ok((x >= y) as i64),
+        _ => return None,
+    })
+}
+
+// Applies a scalar function to every pair of atoms. The shape of one argument
+// has to be a prefix of the other, and atoms of the shorter argument are reused
+// for each cell of the longer argument.
+fn each_pair(x: &Array<i64>, y: &Array<i64>, op: ScalarOp) -> Result<Array<i64>, RuntimeError> {
+    let x_is_longer = x.rank() >= y.rank();
+    let (long, short) = if x_is_longer { (x, y) } else { (y, x) };
+    if !long.shape.starts_with(&short.shape) {
+        return Err(RuntimeError::ShapeMismatch(
+            x.shape.clone(),
+            y.shape.clone(),
+        ));
+    }
+    let repeat = long.shape[short.rank()..].iter().product::<usize>();
+    let data = long
+        .data
+        .iter()
+        .enumerate()
+        .map(|(i, long_atom)| {
+            let short_atom = short.data[i / repeat];
+            if x_is_longer {
+                op(*long_atom, short_atom)
+            } else {
+                op(short_atom, *long_atom)
+            }
+        })
+        .collect::<Result<_, _>>()?;
+    Ok(Array {
+        shape: long.shape.clone(),
+        data,
+    })
+}
+
+// An atom that leaves the other argument alone when it's on the right, which
+// makes it what folding zero items gives back. == and != only manage this for
+// booleans, but that's the same deal J makes.
+pub fn identity(primitive: Primitive) -> Option<i64> {
+    match primitive {
+        Add | Subtract | NotEqual => Some(0),
+        Multiply | Divide | Equal => Some(1),
+        _ => None,
+    }
+}
+
+fn each(
+    x: &Array<i64>,
+    op: fn(i64) -> Result<i64, RuntimeError>,
+) -> Result<Array<i64>, RuntimeError> {
+    Ok(Array {
+        shape: x.shape.clone(),
+        data: x
+            .data
+            .iter()
+            .map(|atom| op(*atom))
+            .collect::<Result<_, _>>()?,
+    })
+}
+
+// The single atom of an array with exactly one element.
+pub fn only_atom(x: &Array<i64>) -> Result<i64, RuntimeError> {
+    match x.data.as_slice() {
+        [atom] => Ok(*atom),
+        _ => Err(RuntimeError::NotScalar(x.shape.clone())),
+    }
+}
+
+pub fn count(x: &Array<i64>) -> Result<usize, RuntimeError> {
+    let atom = only_atom(x)?;
+    usize::try_from(atom).map_err(|_| RuntimeError::NegativeCount(atom))
+}
+
+pub fn items(x: &Array<i64>) -> Vec<Array<i64>> {
+    match x.shape.split_first() {
+        None => vec![x.clone()],
+        Some((_, item_shape)) => x
+            .view()
+            .slice(1)
+            .map(|item| Array {
+                shape: item_shape.to_vec(),
+                data: item.iter().cloned().collect(),
+            })
+            .collect(),
+    }
+}
+
+pub fn from_items(items: Vec<Array<i64>>) -> Result<Array<i64>, RuntimeError> {
+    let item_shape = match items.first() {
+        None => vec![],
+        Some(item) => item.shape.clone(),
+    };
+    let mut shape = vec![items.len()];
+    let mut data = Vec::with_capacity(items.len() * item_shape.iter().product::<usize>());
+    for item in items {
+        if item.shape != item_shape {
+            return Err(RuntimeError::ShapeMismatch(item_shape, item.shape));
+        }
+        data.extend(item.data);
+    }
+    shape.extend(item_shape);
+    Ok(Array { shape, data })
+}
+
+pub fn apply_unary(primitive: Primitive, x: &Array<i64>) -> Result<Array<i64>, RuntimeError> {
+    match primitive {
+        Negate => each(x, |x| x.checked_neg().ok_or(RuntimeError::Overflow)),
+        Sign => each(x, |x| Ok(x.signum())),
+        Abs => each(x, |x| x.checked_abs().ok_or(RuntimeError::Overflow)),
+        Iota => {
+            let n = count(x)?;
+            Ok(Array {
+                shape: vec![n],
+                data: (0..n as i64).collect(),
+            })
+        }
+        _ => Err(RuntimeError::InvalidOperands(primitive.name().to_string())),
+    }
+}
+
+pub fn apply_binary(
+    primitive: Primitive,
+    x: &Array<i64>,
+    y: &Array<i64>,
+) -> Result<Array<i64>, RuntimeError> {
+    match scalar_binary_op(primitive) {
+        Some(op) => each_pair(x, y, op),
+        None => Err(RuntimeError::InvalidOperands(primitive.name().to_string())),
+    }
+}
+
+pub fn scale(coefficient: i64, x: &Array<i64>) -> Result<Array<i64>, RuntimeError> {
+    each_pair(&Array::scalar(coefficient), x, |x, y| {
+        x.checked_mul(y).ok_or(RuntimeError::Overflow)
+    })
+}
@@ -0,0 +1,156 @@
+use super::array::Array;
+use super::interpreter::{Env, RuntimeError};
+use super::primitive::{self, Primitive};
+use petal_syntax::expression::{Builtin, Expression, RichIdentifier};
+use petal_syntax::pos_parser::{Arity, PartOfSpeech, ANY_OPERANDS};
+use std::{fmt, rc::Rc};
+
+#[derive(Clone)]
+pub enum Value<'a> {
+    Noun(Rc<Array<i64>>),
+    // Tuples are nouns too, but they aren't arrays, so most verbs can't do
+    // anything with them. The empty tuple () is the unit value.
+    Tuple(Rc<Vec<Value<'a>>>),
+    Verb(Rc<Verb<'a>>),
+    Adverb(Rc<Adverb<'a>>),
+}
+
+pub enum Verb<'a> {
+    Primitive(Primitive),
+    // a built-in adverb or conjunction applied to its operands
+    Derived(Rc<Adverb<'a>>, Vec<Value<'a>>),
+    Closure(Closure<'a>),
+}
+
+pub enum Adverb<'a> {
+    Primitive(Primitive),
+    Implicit(Builtin),
+    Closure(Closure<'a>),
+}
+
+pub struct Closure<'a> {
+    pub params: &'a [(RichIdentifier, PartOfSpeech)],
+    pub body: &'a Expression,
+    pub pos: PartOfSpeech,
+    pub env: Env<'a>,
+}
+
+impl<'a> Value<'a> {
+    pub fn noun(array: Array<i64>) -> Self {
+        Value::Noun(Rc::new(array))
+    }
+
+    // The parser checks the part of speech of most things, but it can't tell
+    // tuples apart from arrays, and builtin adverbs take anything as operands.
+    pub fn as_noun(&self) -> Result<&Rc<Array<i64>>, RuntimeError> {
+        match self {
+            Value::Noun(array) => Ok(array),
+            Value::Tuple(_) => Err(RuntimeError::NotAnArray(self.to_string())),
+            _ => Err(RuntimeError::NotANoun(self.to_string())),
+        }
+    }
+
+    // The elements of a tuple, or the items of an array.
+    pub fn elements(&self) -> Result<Vec<Value<'a>>, RuntimeError> {
+        match self {
+            Value::Tuple(elements) => Ok(elements.as_ref().clone()),
+            Value::Noun(array) => Ok(primitive::items(array)
+                .into_iter()
+                .map(Value::noun)
+                .collect()),
+            _ => Err(RuntimeError::NotANoun(self.to_string())),
+        }
+    }
+
+    pub fn part_of_speech(&self) -> PartOfSpeech {
+        match self {
+            Value::Noun(_) | Value::Tuple(_) => PartOfSpeech::Noun,
+            Value::Verb(verb) => PartOfSpeech::Verb(verb.arity()),
+            Value::Adverb(adverb) => adverb.part_of_speech(),
+        }
+    }
+}
+
+impl Verb<'_> {
+    pub fn arity(&self) -> Arity {
+        let pos = match self {
+            Verb::Primitive(primitive) => primitive.part_of_speech(),
+            Verb::Derived(adverb, _) => return adverb.result_arity(),
+            Verb::Closure(closure) => closure.pos,
+        };
+        match pos {
+            PartOfSpeech::Verb(arity) => arity,
+            _ => panic!("verb with part of speech {}", pos),
+        }
+    }
+}
+
+impl Adverb<'_> {
+    pub fn part_of_speech(&self) -> PartOfSpeech {
+        use Arity::*;
+        match self {
+            Adverb::Primitive(primitive) => primitive.part_of_speech(),
+            Adverb::Implicit(Builtin::Scale) => PartOfSpeech::Adverb(Unary, Unary, ANY_OPERANDS),
+            Adverb::Implicit(_) => PartOfSpeech::Adverb(Binary, self.result_arity(), ANY_OPERANDS),
+            Adverb::Closure(closure) => closure.pos,
+        }
+    }
+
+    pub fn result_arity(&self) -> Arity {
+        use Builtin::*;
+        match self {
+            Adverb::Implicit(ComposeLeft | ComposeRight) => Arity::Binary,
+            Adverb::Implicit(_) => Arity::Unary,
+            _ => match self.part_of_speech() {
+                PartOfSpeech::Adverb(_, result_arity, _) => result_arity,
+                pos => panic!("adverb with part of speech {}", pos),
+            },
+        }
+    }
+}
+
+impl fmt::Display for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Noun(array) => write!(f, "{}", array),
+            Value::Tuple(elements) => {
+                write!(f, "(")?;
+                for (i, element) in elements.iter().enumerate() {
+                    if i != 0 {
+                        write!(f, " ")?;
+                    }
+                    match element {
+                        Value::Noun(array) => write!(f, "{}", literal(array))?,
+                        element => write!(f, "{}", element)?,
+                    }
+                }
+                write!(f, ")")
+            }
+            _ => write!(f, "<{}>", self.part_of_speech()),
+        }
+    }
+}
+
+impl fmt::Display for Adverb<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Adverb::Primitive(primitive) => write!(f, "{}", primitive.name()),
+            Adverb::Implicit(builtin) => write!(f, "<{}>", builtin),
+            Adverb::Closure(closure) => write!(f, "<{}>", closure.pos),
+        }
+    }
+}
+
+// Arrays inside of tuples are written the way they'd appear in source, since
+// the usual grid of atoms doesn't say where one element ends and the next one
+// begins.
+fn literal(array: &Array<i64>) -> String {
+    match array.shape.as_slice() {
+        [] => array.data[0].to_string(),
+        _ => {
+            let items = primitive::items(array);
+            let items = items.iter().map(literal).collect::<Vec<_>>();
+            format!("[{}]", items.join(" "))
+        }
+    }
+}
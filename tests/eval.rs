@@ -138,6 +138,22 @@ fn add_vector_matrix_rank_one_two() {
     );
 }
 
+#[test]
+fn add_empty_frames() {
+    k9::snapshot!(
+        test_ranked_add(idot(vec![0, 3]), idot(vec![3]), 1, 1),
+        "[0, 3]$[]"
+    );
+    k9::snapshot!(
+        test_ranked_add(idot(vec![2, 0]), scalar(1), 0, 0),
+        "[2, 0]$[]"
+    );
+    k9::snapshot!(
+        test_ranked_add(idot(vec![0]), idot(vec![0, 2]), 0, 0),
+        "[0, 2]$[]"
+    );
+}
+
 #[test]
 fn math() {
     use rug::{Assign, Integer, Rational};
@@ -0,0 +1,35 @@
+use petal::highlight::{ansi, html};
+
+#[test]
+fn html_output() {
+    k9::snapshot!(
+        html("x = 1\nfold + (neg sign) x\n").unwrap(),
+        r#"<pre class="petal"><span class="n">x</span> = <span class="n">1</span>
+<span class="a1">fold</span> <span class="v2">+</span> (<span class="v1">neg</span><span class="implicit compose"> </span><span class="v1">sign</span>) <span class="n">x</span>
+</pre>
+"#
+    );
+    k9::snapshot!(
+        html("a = 1\nb = 2\na < b").unwrap(),
+        r#"<pre class="petal"><span class="n">a</span> = <span class="n">1</span>
+<span class="n">b</span> = <span class="n">2</span>
+<span class="n">a</span> <span class="v2">&lt;</span> <span class="n">b</span></pre>
+"#
+    );
+}
+
+#[test]
+fn ansi_output() {
+    k9::snapshot!(
+        format!("{:?}", ansi("2x + 1\nx = 3").unwrap()),
+        r#""\u{1b}[32;100m2\u{1b}[0m\u{1b}[36mx\u{1b}[0m \u{1b}[33m+\u{1b}[0m \u{1b}[36m1\u{1b}[0m\n\u{1b}[36mx\u{1b}[0m = \u{1b}[36m3\u{1b}[0m""#
+    );
+}
+
+#[test]
+fn invalid_syntax() {
+    k9::snapshot!(
+        html("x = (1 +\n").unwrap_err().to_string(),
+        "invalid syntax on line 1"
+    );
+}
@@ -0,0 +1,471 @@
+use petal::interpreter::{Interpreter, Limits};
+use petal::primitive::Primitive;
+use petal_syntax::prelude::PRELUDE;
+
+fn eval_with(limits: Limits, source: &str) -> String {
+    let (expr, _) = petal_syntax::parse(source).unwrap();
+    match Interpreter::new(limits).run(&expr) {
+        Ok(value) => value.to_string(),
+        Err(error) => format!("error: {}", error),
+    }
+}
+
+fn eval(source: &str) -> String {
+    eval_with(Limits::default(), source)
+}
+
+// every result of a script on its own line, up until the first error
+fn run_script(source: &str) -> String {
+    let script = petal_syntax::parse_script(source).unwrap();
+    let mut output = vec![];
+    let result = Interpreter::new(Limits::default())
+        .run_script(&script, |value| output.push(value.to_string()));
+    if let Err(error) = result {
+        output.push(format!("error: {}", error));
+    }
+    output.join("\n")
+}
+
+#[test]
+fn every_prelude_name_is_a_primitive() {
+    for (name, pos) in PRELUDE {
+        let primitive = Primitive::of_name(name).unwrap();
+        assert_eq!(primitive.part_of_speech(), *pos);
+    }
+}
+
+#[test]
+fn arithmetic() {
+    k9::snapshot!(eval("1 + 2 * 3"), "7");
+    k9::snapshot!(eval("(1 + 2) * 3"), "9");
+    k9::snapshot!(eval("7 / 2"), "3");
+    k9::snapshot!(eval("neg 7 / 2"), "-3");
+    k9::snapshot!(eval("(neg 7) / 2"), "-4");
+    k9::snapshot!(eval("(neg 7) % 3"), "2");
+    k9::snapshot!(eval("1 / 0"), "error: division by zero");
+}
+
+#[test]
+fn arrays() {
+    k9::snapshot!(eval("[1 2 3] + 10"), "11 12 13");
+    k9::snapshot!(
+        eval("[1 2 3; 4 5 6] * [1 10 100]"),
+        "error: shape mismatch: [2, 3] and [3]"
+    );
+    k9::snapshot!(
+        eval("[1 2 3; 4 5 6] * [1 10]"),
+        "
+ 1  2  3
+40 50 60
+"
+    );
+    k9::snapshot!(
+        eval("[1 2 3; 40 50 60] + 1"),
+        "
+ 2  3  4
+41 51 61
+"
+    );
+    k9::snapshot!(eval("iota 5"), "0 1 2 3 4");
+    k9::snapshot!(eval("fold + iota 5"), "10");
+    k9::snapshot!(eval("fold - [1 2 3]"), "2");
+    k9::snapshot!(
+        eval(
+            "
+m = [
+  1 2;
+  3 4
+]
+m + (10 *
+     m)
+"
+        ),
+        "
+11 22
+33 44
+"
+    );
+}
+
+#[test]
+fn implicit_verbs() {
+    k9::snapshot!(eval("(* 2) 5"), "10");
+    k9::snapshot!(eval("(10 -) 3"), "7");
+    k9::snapshot!(eval("x = 4\n-x + 3x"), "8");
+    k9::snapshot!(eval("(neg abs) 5"), "-5");
+    k9::snapshot!(eval("10 (- neg) 3"), "13");
+    k9::snapshot!(eval("~(+ neg) 3"), "0");
+    k9::snapshot!(eval("10 flip - 3"), "-7");
+    k9::snapshot!(eval("3 (+ . abs) neg 4"), "7");
+}
+
+#[test]
+fn definitions() {
+    k9::snapshot!(
+        eval(
+            "
+hypot (x : n) (y : n) : n = (x * x) + (y * y)
+3 hypot 4
+"
+        ),
+        "25"
+    );
+    k9::snapshot!(
+        eval(
+            "
+twice (f : v1) : v1 = f f
+twice (+ 1) 5
+"
+        ),
+        "7"
+    );
+    k9::snapshot!(
+        eval(
+            "
+offset (f : v2) (k : n) : v1 = f k
+(- offset 1) 10
+"
+        ),
+        "9"
+    );
+    k9::snapshot!(eval("twice (f : v1) : v1 = f f\ntwice neg"), "<v1>");
+}
+
+#[test]
+fn operators() {
+    k9::snapshot!(
+        eval("<+> (x : n) (y : n) : n = (x * 10) + y\n1 <+> 2"),
+        "12"
+    );
+    // the longest operator wins, even over the prelude
+    k9::snapshot!(
+        eval("<=> (x : n) (y : n) : n = sign y - x\n[1 2 3] <=> 2"),
+        "1 0 -1"
+    );
+    // operators can be used before they're defined, and declared up front
+    k9::snapshot!(
+        eval("<+> : v2\n<=> : v2\n2 <=> 1 <+> 2\n<+> = +\n<=> = -"),
+        "-1"
+    );
+    k9::snapshot!(
+        eval("f (x : n) : n = x <+> 1\n<+> (x : n) (y : n) : n = (x * 10) + y\nf 2"),
+        "21"
+    );
+}
+
+#[test]
+fn conditionals() {
+    k9::snapshot!(
+        eval(
+            "
+sgn (x : n) : n =
+  if x < 0
+    neg 1
+  else if x == 0
+    0
+  else
+    1
+[(sgn neg 5) (sgn 0) (sgn 5)]
+"
+        ),
+        "-1 0 1"
+    );
+    k9::snapshot!(
+        eval(
+            "
+if [1 2]
+  1
+else
+  2
+"
+        ),
+        "error: expected a single element, got shape [2]"
+    );
+}
+
+#[test]
+fn conditional_branches_are_lazy() {
+    k9::snapshot!(
+        eval(
+            "
+x = 0
+if x == 0
+  0
+else
+  1 / x
+"
+        ),
+        "0"
+    );
+}
+
+#[test]
+fn iteration() {
+    k9::snapshot!(eval("((* 2) power 10) 1"), "1024");
+    k9::snapshot!(eval("((* 2) power 0) 1"), "1");
+    k9::snapshot!(
+        eval(
+            "
+isqrt (n : n) : n =
+  step (x : n) : n = ((x + (n / x)) / 2) min x
+  fix step n
+[(isqrt 10) (isqrt 100) (isqrt 1000)]
+"
+        ),
+        "3 10 31"
+    );
+    k9::snapshot!(
+        eval_with(
+            Limits {
+                max_iterations: 5,
+                ..Limits::default()
+            },
+            "fix (+ 1) 0"
+        ),
+        "error: no fixed point after 5 iterations"
+    );
+}
+
+#[test]
+fn builtin_adverbs_check_their_operands() {
+    k9::snapshot!(
+        eval("(neg power neg) 1"),
+        "error: expected a noun, got <v1>"
+    );
+    k9::snapshot!(eval("1 (1 . 2) 3"), "error: invalid operands to .");
+    k9::snapshot!(eval("1 (neg . neg) 2"), "error: invalid operands to .");
+}
+
+#[test]
+fn recursion() {
+    k9::snapshot!(
+        eval(
+            "
+fact (x : n) : n =
+  if x
+    x * fact x - 1
+  else
+    1
+fact 10
+"
+        ),
+        "3628800"
+    );
+    k9::snapshot!(
+        eval(
+            "
+even (x : n) : n =
+  if x
+    odd x - 1
+  else
+    1
+odd (x : n) : n =
+  if x
+    even x - 1
+  else
+    0
+[(even 10) (odd 10) (even 7)]
+"
+        ),
+        "1 0 0"
+    );
+}
+
+#[test]
+fn recursion_limit() {
+    let forever = "
+loop (x : n) : n = loop x + 1
+loop 0
+";
+    // the default limit needs more stack than the test harness gives us
+    let result = std::thread::Builder::new()
+        .stack_size(64 << 20)
+        .spawn(move || eval(forever))
+        .unwrap()
+        .join()
+        .unwrap();
+    k9::snapshot!(result, "error: recursion deeper than 1000 calls");
+    k9::snapshot!(
+        eval_with(
+            Limits {
+                max_depth: 10,
+                ..Limits::default()
+            },
+            forever
+        ),
+        "error: recursion deeper than 10 calls"
+    );
+    let count = "
+count (x : n) : n =
+  if x
+    1 + count x - 1
+  else
+    0
+";
+    let shallow = Limits {
+        max_depth: 10,
+        ..Limits::default()
+    };
+    k9::snapshot!(eval_with(shallow, &format!("{}count 9", count)), "9");
+    k9::snapshot!(
+        eval_with(shallow, &format!("{}count 10", count)),
+        "error: recursion deeper than 10 calls"
+    );
+}
+
+#[test]
+fn blocks() {
+    k9::snapshot!(
+        eval(
+            "
+x = 1
+y = x + 10
+x = 2
+z = x + w
+w = 100
+[y z]
+"
+        ),
+        "11 102"
+    );
+    k9::snapshot!(
+        eval(
+            "
+a = 1
+b =
+  a = 10
+  a + c
+c = 100
+[a b]
+"
+        ),
+        "1 110"
+    );
+    k9::snapshot!(
+        eval(
+            "
+f : v1 = g
+g : v1 = neg
+f 5
+"
+        ),
+        "-5"
+    );
+}
+
+#[test]
+fn scripts() {
+    k9::snapshot!(
+        run_script(
+            "
+x = 1
+x + 1
+y =
+  x * 10
+  x * 100
+y
+neg
+"
+        ),
+        "
+2
+100
+<v1>
+"
+    );
+    k9::snapshot!(
+        run_script("1\n1 / 0\n3"),
+        "
+1
+error: division by zero
+"
+    );
+}
+
+#[test]
+fn unit() {
+    k9::snapshot!(eval("()"), "()");
+    k9::snapshot!(eval("x = ()\nx"), "()");
+    k9::snapshot!(run_script("()\n1"), "\n()\n1\n");
+    k9::snapshot!(eval("neg ()"), "error: () is not an array");
+    k9::snapshot!(eval("[1 ()]"), "error: () is not an array");
+    k9::snapshot!(eval("(+ ()) 1"), "error: () is not an array");
+    k9::snapshot!(eval("if ()\n  1\nelse\n  2"), "error: () is not an array");
+}
+
+#[test]
+fn tuples() {
+    k9::snapshot!(eval("(1 [2 3] [1 2; 3 4])"), "(1 [2 3] [[1 2] [3 4]])");
+    k9::snapshot!(eval("first (1 2)"), "1");
+    k9::snapshot!(eval("second ([1 2] (3 4))"), "(3 4)");
+    k9::snapshot!(eval("length (1 2 3)"), "3");
+    k9::snapshot!(eval("first ()"), "error: index 0 is out of bounds for length 0");
+    k9::snapshot!(eval("(1 2) + 1"), "error: (1 2) is not an array");
+    // on arrays, they work with items
+    k9::snapshot!(eval("first [1 2; 3 4]"), "1 2");
+    k9::snapshot!(eval("length iota 5"), "5");
+    k9::snapshot!(
+        eval(
+            "
+swap (t : n) : n = ((second t) (first t))
+swap (1 2)
+"
+        ),
+        "(2 1)"
+    );
+}
+
+#[test]
+fn destructuring() {
+    k9::snapshot!(
+        eval(
+            "
+divmod (x : n) (y : n) : n = ((x / y) (x % y))
+(q r) = 7 divmod 2
+[q r]
+"
+        ),
+        "3 1"
+    );
+    k9::snapshot!(eval("(a b) = [10 20]\nb"), "20");
+    k9::snapshot!(
+        eval("(a b) = (1 2 3)\na"),
+        "error: expected 2 elements, got 3"
+    );
+}
+
+#[test]
+fn empty_arrays() {
+    k9::snapshot!(eval("fold + []"), "0");
+    k9::snapshot!(eval("fold * iota 0"), "1");
+    k9::snapshot!(eval("fold + ()"), "0");
+    k9::snapshot!(eval("fold max []"), "error: cannot fold an empty array");
+    k9::snapshot!(
+        eval("fold (flip -) []"),
+        "error: cannot fold an empty array"
+    );
+    k9::snapshot!(
+        eval("[1 2] + iota 0"),
+        "error: shape mismatch: [2] and [0]"
+    );
+}
+
+#[test]
+fn unused_assignments_are_evaluated() {
+    k9::snapshot!(eval("x = 1 / 0\n1"), "error: division by zero");
+}
+
+#[test]
+fn cyclic_definitions() {
+    k9::snapshot!(eval("f : v1 = f\nf 1"), "error: f is defined in terms of itself");
+    k9::snapshot!(
+        eval(
+            "
+f : v1 = neg g
+g : v1 = abs f
+f 1
+"
+        ),
+        "error: f is defined in terms of itself"
+    );
+}
@@ -0,0 +1,258 @@
+use lsp_server::{Connection, Message, Notification, Request, RequestId};
+use lsp_types::{HoverContents, MarkedString, Position, Range, TextDocumentContentChangeEvent};
+use petal::lsp::{offset_of_position, position_of_offset, serve, Document};
+use serde_json::json;
+
+fn position(line: u32, character: u32) -> Position {
+    Position { line, character }
+}
+
+fn show_range(range: Range) -> String {
+    format!(
+        "{}:{}-{}:{}",
+        range.start.line, range.start.character, range.end.line, range.end.character
+    )
+}
+
+fn hover(document: &Document, line: u32, character: u32) -> String {
+    match document.hover(position(line, character)) {
+        Some(hover) => match hover.contents {
+            HoverContents::Scalar(MarkedString::String(contents)) => contents,
+            contents => panic!("unexpected hover {:?}", contents),
+        },
+        None => "none".to_string(),
+    }
+}
+
+fn diagnostics(text: &str) -> String {
+    Document::new(text.to_string())
+        .diagnostics()
+        .iter()
+        .map(|diagnostic| format!("{} {}", show_range(diagnostic.range), diagnostic.message))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+const PROGRAM: &str = "\
+double (x : n) : n = x * 2
+y = double 10
+y + 1
+";
+
+#[test]
+fn positions() {
+    let text = "ab\né = 1\n";
+    k9::snapshot!(
+        format!("{:?}", position_of_offset(text, 0)),
+        "Position { line: 0, character: 0 }"
+    );
+    k9::snapshot!(
+        format!("{:?}", position_of_offset(text, 3)),
+        "Position { line: 1, character: 0 }"
+    );
+    // é is two bytes but one UTF-16 code unit
+    k9::snapshot!(
+        format!("{:?}", position_of_offset(text, 5)),
+        "Position { line: 1, character: 1 }"
+    );
+    for offset in [0, 1, 3, 5, 6, text.len()] {
+        assert_eq!(
+            offset_of_position(text, position_of_offset(text, offset)),
+            offset
+        );
+    }
+    assert_eq!(offset_of_position(text, position(0, 100)), 2);
+    assert_eq!(offset_of_position(text, position(100, 0)), text.len());
+}
+
+#[test]
+fn hovers() {
+    let document = Document::new(PROGRAM.to_string());
+    k9::snapshot!(hover(&document, 0, 0), "double : v1");
+    k9::snapshot!(hover(&document, 0, 8), "x : n");
+    k9::snapshot!(hover(&document, 0, 23), "* : v2");
+    k9::snapshot!(hover(&document, 1, 5), "double : v1");
+    k9::snapshot!(hover(&document, 2, 0), "y : n");
+    k9::snapshot!(hover(&document, 0, 19), "none");
+}
+
+#[test]
+fn definitions_and_references() {
+    let document = Document::new(PROGRAM.to_string());
+    let definition = |line, character| {
+        document
+            .definition(position(line, character))
+            .map_or("none".to_string(), show_range)
+    };
+    k9::snapshot!(definition(1, 5), "0:0-0:6");
+    k9::snapshot!(definition(0, 21), "0:8-0:9");
+    k9::snapshot!(definition(2, 0), "1:0-1:1");
+    // the prelude isn't defined anywhere
+    k9::snapshot!(definition(0, 23), "none");
+
+    let references = |line, character, include_declaration| {
+        document
+            .references(position(line, character), include_declaration)
+            .into_iter()
+            .map(show_range)
+            .collect::<Vec<_>>()
+            .join(" ")
+    };
+    k9::snapshot!(references(0, 0, true), "0:0-0:6 1:4-1:10");
+    k9::snapshot!(references(0, 0, false), "1:4-1:10");
+    k9::snapshot!(references(2, 0, true), "1:0-1:1 2:0-2:1");
+}
+
+#[test]
+fn diagnostics_point_at_the_problem() {
+    k9::snapshot!(diagnostics(PROGRAM), "");
+    k9::snapshot!(
+        diagnostics("x = 1\ny = x + zed\ny"),
+        "1:8-1:11 zed is not defined"
+    );
+    k9::snapshot!(
+        diagnostics("f : v1 = 1\nf 2"),
+        "0:0-0:1 declared as v1 but defined as n"
+    );
+    k9::snapshot!(
+        diagnostics("x = 1\ny = x +)\ny"),
+        "1:2-1:8 invalid syntax on line 2"
+    );
+    k9::snapshot!(
+        diagnostics("x =\n  y = 1\n\ty\nx"),
+        "2:0-2:2 indentation mixes tabs and spaces on line 3"
+    );
+    k9::snapshot!(
+        diagnostics("(a b a) = (1 2 3)\na"),
+        "0:5-0:6 a is bound twice on line 1"
+    );
+}
+
+#[test]
+fn edits() {
+    let mut document = Document::new(PROGRAM.to_string());
+    let change = |start, end, text: &str| TextDocumentContentChangeEvent {
+        range: Some(Range {
+            start: position(0, start),
+            end: position(0, end),
+        }),
+        range_length: None,
+        text: text.to_string(),
+    };
+    // moving everything down a line
+    document.edit(vec![change(0, 0, "z = 1\n")]);
+    k9::snapshot!(hover(&document, 1, 0), "double : v1");
+    k9::snapshot!(hover(&document, 3, 0), "y : n");
+    k9::snapshot!(
+        document
+            .references(position(1, 0), true)
+            .into_iter()
+            .map(show_range)
+            .collect::<Vec<_>>()
+            .join(" "),
+        "1:0-1:6 2:4-2:10"
+    );
+
+    document.edit(vec![change(4, 5, "zed")]);
+    k9::snapshot!(
+        format!("{:?}", document.diagnostics()[0].message),
+        r#""zed is not defined""#
+    );
+    document.edit(vec![change(4, 7, "1")]);
+    k9::snapshot!(document.diagnostics().len(), "0");
+}
+
+#[test]
+fn conditionals() {
+    let text = "x = 1\nif x\n  neg x\nelse\n  x\n";
+    k9::snapshot!(diagnostics(text), "");
+
+    // typing the else one character at a time
+    let mut document = Document::new(text.replace("else", ""));
+    for (character, c) in "else".chars().enumerate() {
+        document.edit(vec![TextDocumentContentChangeEvent {
+            range: Some(Range {
+                start: position(3, character as u32),
+                end: position(3, character as u32),
+            }),
+            range_length: None,
+            text: c.to_string(),
+        }]);
+    }
+    k9::snapshot!(document.diagnostics().len(), "0");
+}
+
+#[test]
+fn malformed_messages() {
+    let (client, server) = Connection::memory();
+    let server = std::thread::spawn(move || serve(server).unwrap());
+    let request = |id: i32, method: &str, params| {
+        let request = Request::new(RequestId::from(id), method.to_string(), params);
+        client.sender.send(Message::Request(request)).unwrap();
+        match client.receiver.recv().unwrap() {
+            Message::Response(response) => match response.error {
+                Some(error) => format!("error {}", error.code),
+                None => response.result.unwrap().to_string(),
+            },
+            message => panic!("unexpected message {:?}", message),
+        }
+    };
+    let notify = |method: &str, params| {
+        let notification = Notification::new(method.to_string(), params);
+        client
+            .sender
+            .send(Message::Notification(notification))
+            .unwrap();
+    };
+
+    request(1, "initialize", json!({ "capabilities": {} }));
+    notify("initialized", json!({}));
+
+    // a notification that doesn't make sense is ignored
+    notify("textDocument/didOpen", json!({ "textDocument": 1 }));
+    // and a request fails with an error
+    k9::snapshot!(
+        request(2, "textDocument/hover", json!({ "position": "here" })),
+        "error -32602"
+    );
+
+    // but the server keeps going
+    let uri = "file:///test.petal";
+    notify(
+        "textDocument/didOpen",
+        json!({ "textDocument": {
+            "uri": uri, "languageId": "petal", "version": 1, "text": "x = 1\nx"
+        }}),
+    );
+    let diagnostics = client.receiver.recv().unwrap();
+    assert!(matches!(diagnostics, Message::Notification(_)));
+
+    // a change whose range is backwards replaces the text between its ends
+    notify(
+        "textDocument/didChange",
+        json!({
+            "textDocument": { "uri": uri, "version": 2 },
+            "contentChanges": [{
+                "range": {
+                    "start": { "line": 0, "character": 5 },
+                    "end": { "line": 0, "character": 4 }
+                },
+                "text": "2"
+            }]
+        }),
+    );
+    let diagnostics = client.receiver.recv().unwrap();
+    assert!(matches!(diagnostics, Message::Notification(_)));
+    k9::snapshot!(
+        request(
+            3,
+            "textDocument/hover",
+            json!({ "textDocument": { "uri": uri }, "position": { "line": 1, "character": 0 } })
+        ),
+        r#"{"contents":"x : n","range":{"end":{"character":1,"line":1},"start":{"character":0,"line":1}}}"#
+    );
+
+    request(4, "shutdown", json!(null));
+    notify("exit", json!(null));
+    server.join().unwrap();
+}